@@ -0,0 +1,111 @@
+use crate::error::Error;
+
+/// Keeps track of the automatic numbering of messages, as configured by the `autonumber`
+/// statements in the diagram.
+pub struct Autonumber {
+    levels: Vec<u32>,
+    step: u32,
+    format: Option<String>,
+    active: bool,
+    /// Set when the last level can't be incremented any further, so that numbering
+    /// another message is an error.
+    overflowed: bool,
+}
+
+impl Autonumber {
+    pub fn new() -> Autonumber {
+        Autonumber {
+            levels: vec![1],
+            step: 1,
+            format: None,
+            active: false,
+            overflowed: false,
+        }
+    }
+
+    /// Starts numbering the messages from the start value, which may be hierarchical
+    /// (e.g. 1.2.1). Only the last level of the number is incremented by the step.
+    pub fn start(&mut self, start: Option<Vec<u32>>, step: Option<u32>, format: Option<String>) {
+        self.levels = start.unwrap_or_else(|| vec![1]);
+        self.step = step.unwrap_or(1);
+        self.format = format;
+        self.active = true;
+        self.overflowed = false;
+    }
+
+    pub fn stop(&mut self) {
+        self.active = false;
+    }
+
+    /// Continues numbering from where it was stopped, optionally changing the step and
+    /// the format.
+    pub fn resume(&mut self, step: Option<u32>, format: Option<String>) {
+        if let Some(step) = step {
+            self.step = step;
+        }
+        if format.is_some() {
+            self.format = format;
+        }
+        self.active = true;
+    }
+
+    /// Increments the level of a hierarchical number and resets all the levels below it.
+    pub fn increment_level(&mut self, level: usize) -> Result<(), Error> {
+        let value = self.levels.get_mut(level).ok_or_else(|| {
+            Error::new("autonumber inc for a level the number doesn't have".to_string())
+        })?;
+        *value = value
+            .checked_add(1)
+            .ok_or_else(|| Error::new("autonumber inc overflows the number".to_string()))?;
+        for l in &mut self.levels[level + 1..] {
+            *l = 1;
+        }
+        self.overflowed = false;
+        Ok(())
+    }
+
+    /// Returns the formatted number for the next message, or None if numbering is off.
+    pub fn next_number(&mut self) -> Result<Option<String>, Error> {
+        if !self.active {
+            return Ok(None);
+        }
+        if self.overflowed {
+            return Err(Error::new("Message number overflows".to_string()));
+        }
+        let (last, parents) = self.levels.split_last().unwrap();
+        let prefix: String = parents.iter().map(|l| format!("{}.", l)).collect();
+        let number = match &self.format {
+            Some(format) => apply_format(format, &prefix, *last),
+            None => format!("{}{}", prefix, last),
+        };
+        let last = self.levels.last_mut().unwrap();
+        match last.checked_add(self.step) {
+            Some(next) => *last = next,
+            None => self.overflowed = true,
+        }
+        Ok(Some(number))
+    }
+}
+
+/// Substitutes the first run of digit placeholders in the format with the number.
+/// Every `0` in the placeholder is a zero-padded digit, while `#` digits are optional.
+fn apply_format(format: &str, prefix: &str, value: u32) -> String {
+    let is_placeholder = |c: char| c == '0' || c == '#';
+    match format.find(is_placeholder) {
+        Some(start) => {
+            let end = format[start..]
+                .find(|c| !is_placeholder(c))
+                .map_or(format.len(), |len| start + len);
+            let width = format[start..end].matches('0').count();
+            format!(
+                "{}{}{:0width$}{}",
+                &format[..start],
+                prefix,
+                value,
+                &format[end..],
+                width = width
+            )
+        }
+        None => format!("{}{}{}", format, prefix, value),
+    }
+}
@@ -1,37 +1,108 @@
+use crate::decoration::DecorationConfig;
+use crate::delay::DelayConfig;
 use crate::group::GroupConfig;
 use crate::message::MessageConfig;
 use crate::note::NoteConfig;
 use crate::participant::ParticipantConfig;
+use crate::participant_box::ParticipantBoxConfig;
+use crate::rendering::font::Font;
+use crate::rendering::renderer::{
+    DARK_GREY, LIGHT_BLUE, LIGHT_GREY, LIGHT_PURPLE, MEDIUM_BLUE, MEDIUM_PURPLE,
+};
 use crate::separator::SeparatorConfig;
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct Config {
+    pub decoration_config: DecorationConfig,
+    pub delay_config: DelayConfig,
     pub group_config: GroupConfig,
     pub message_config: MessageConfig,
     pub note_config: NoteConfig,
     pub participant_config: ParticipantConfig,
+    pub participant_box_config: ParticipantBoxConfig,
     pub separator_config: SeparatorConfig,
 }
 
 static DEFAULT_FONT_SIZE: u32 = 12;
 
+impl Config {
+    /// Sets the font size of all the elements, keeping participant labels slightly larger.
+    pub fn set_font_size(&mut self, font_size: u32) {
+        self.decoration_config.font_size = font_size;
+        self.delay_config.font_size = font_size;
+        self.group_config.font_size = font_size;
+        self.message_config.font_size = font_size;
+        self.note_config.font_size = font_size;
+        self.participant_config.font_size = font_size * 4 / 3;
+        self.participant_box_config.font_size = font_size;
+        self.separator_config.font_size = font_size;
+    }
+
+    /// Sets the font of all the elements.
+    pub fn set_font(&mut self, font: Font) {
+        self.decoration_config.font = font.clone();
+        self.delay_config.font = font.clone();
+        self.group_config.font = font.clone();
+        self.message_config.font = font.clone();
+        self.note_config.font = font.clone();
+        self.participant_config.font = font.clone();
+        self.participant_box_config.font = font.clone();
+        self.separator_config.font = font;
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Config {
+            decoration_config: DecorationConfig {
+                font_size: DEFAULT_FONT_SIZE,
+                font: Font::default(),
+            },
+            delay_config: DelayConfig {
+                font_size: DEFAULT_FONT_SIZE,
+                font: Font::default(),
+            },
             group_config: GroupConfig {
                 font_size: DEFAULT_FONT_SIZE,
+                font: Font::default(),
+                background: LIGHT_PURPLE.to_string(),
+                border: MEDIUM_PURPLE.to_string(),
             },
             message_config: MessageConfig {
                 font_size: DEFAULT_FONT_SIZE,
+                font: Font::default(),
+                max_message_width: None,
+                response_below_arrow: false,
+                strict_uml: false,
             },
             note_config: NoteConfig {
                 font_size: DEFAULT_FONT_SIZE,
+                font: Font::default(),
+                max_note_width: None,
+                background: LIGHT_GREY.to_string(),
+                border: DARK_GREY.to_string(),
             },
             participant_config: ParticipantConfig {
                 font_size: DEFAULT_FONT_SIZE * 4 / 3,
+                font: Font::default(),
+                padding: 20,
+                background: LIGHT_BLUE.to_string(),
+                border: MEDIUM_BLUE.to_string(),
+                lifeline_colour: MEDIUM_BLUE.to_string(),
+                lifeline_dashed: false,
+            },
+            participant_box_config: ParticipantBoxConfig {
+                font_size: DEFAULT_FONT_SIZE,
+                font: Font::default(),
+                padding: 10,
+                background: LIGHT_GREY.to_string(),
+                border: DARK_GREY.to_string(),
             },
             separator_config: SeparatorConfig {
                 font_size: DEFAULT_FONT_SIZE,
+                font: Font::default(),
+                background: LIGHT_PURPLE.to_string(),
+                border: MEDIUM_PURPLE.to_string(),
             },
         }
     }
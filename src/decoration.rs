@@ -0,0 +1,209 @@
+use crate::rendering::font::Font;
+use crate::rendering::layout::{text_width, GridSize};
+use crate::rendering::renderer::{RectParams, Renderer, DARK_GREY, LIGHT_GREY};
+
+static DECORATION_MARGIN: u32 = 10;
+static LEGEND_PADDING: u32 = 5;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Alignment {
+    Left,
+    Center,
+    Right,
+}
+
+pub struct Legend {
+    pub text: String,
+    pub alignment: Alignment,
+}
+
+/// Diagram-level texts that live outside the timeline: the header and the title are drawn
+/// above the participants, the legend, the caption and the footer below them.
+pub struct Decorations {
+    pub title: Option<String>,
+    pub header: Option<String>,
+    pub footer: Option<String>,
+    pub caption: Option<String>,
+    pub legend: Option<Legend>,
+    config: DecorationConfig,
+}
+
+/// A block of (possibly multiline) text, stacked vertically with the other decorations.
+struct TextBlock<'a> {
+    text: &'a str,
+    font: &'a Font,
+    font_size: u32,
+    alignment: Alignment,
+    boxed: bool,
+}
+
+impl TextBlock<'_> {
+    fn padding(&self) -> u32 {
+        if self.boxed {
+            LEGEND_PADDING
+        } else {
+            0
+        }
+    }
+
+    fn width(&self) -> u32 {
+        text_width(self.text, self.font, self.font_size) + self.padding() * 2
+    }
+
+    fn height(&self) -> u32 {
+        let lines = self.text.split('\n').count() as u32;
+        self.font_size * lines * 11 / 10 + self.padding() * 2
+    }
+
+    fn draw(&self, renderer: &mut dyn Renderer, y: u32, diagram_width: u32) {
+        let width = self.width();
+        let x = match self.alignment {
+            Alignment::Left => DECORATION_MARGIN,
+            Alignment::Center => diagram_width.saturating_sub(width) / 2,
+            Alignment::Right => diagram_width.saturating_sub(width + DECORATION_MARGIN),
+        };
+
+        if self.boxed {
+            // Boxed text is always left aligned within its box
+            let params = RectParams {
+                fill: LIGHT_GREY,
+                stroke: DARK_GREY,
+                ..Default::default()
+            };
+            renderer.render_rect(x, y, width, self.height(), params);
+            let padding = self.padding();
+            renderer.render_text(
+                self.text,
+                x + padding,
+                y + padding,
+                self.font,
+                self.font_size,
+                "left",
+            );
+        } else {
+            let (text_x, anchor) = match self.alignment {
+                Alignment::Left => (x, "left"),
+                Alignment::Center => (x + width / 2, "middle"),
+                Alignment::Right => (x + width, "end"),
+            };
+            renderer.render_text(self.text, text_x, y, self.font, self.font_size, anchor);
+        }
+    }
+}
+
+impl Decorations {
+    pub(crate) fn new(config: DecorationConfig) -> Decorations {
+        Decorations {
+            title: None,
+            header: None,
+            footer: None,
+            caption: None,
+            legend: None,
+            config,
+        }
+    }
+
+    fn top_blocks(&self) -> Vec<TextBlock<'_>> {
+        let font_size = self.config.font_size;
+        let mut blocks = vec![];
+        if let Some(header) = &self.header {
+            blocks.push(TextBlock {
+                text: header,
+                font: &self.config.font,
+                font_size: font_size * 10 / 12,
+                alignment: Alignment::Right,
+                boxed: false,
+            });
+        }
+        if let Some(title) = &self.title {
+            blocks.push(TextBlock {
+                text: title,
+                font: &self.config.font,
+                font_size: font_size * 3 / 2,
+                alignment: Alignment::Center,
+                boxed: false,
+            });
+        }
+        blocks
+    }
+
+    fn bottom_blocks(&self) -> Vec<TextBlock<'_>> {
+        let font_size = self.config.font_size;
+        let mut blocks = vec![];
+        if let Some(legend) = &self.legend {
+            blocks.push(TextBlock {
+                text: &legend.text,
+                font: &self.config.font,
+                font_size,
+                alignment: legend.alignment,
+                boxed: true,
+            });
+        }
+        if let Some(caption) = &self.caption {
+            blocks.push(TextBlock {
+                text: caption,
+                font: &self.config.font,
+                font_size,
+                alignment: Alignment::Center,
+                boxed: false,
+            });
+        }
+        if let Some(footer) = &self.footer {
+            blocks.push(TextBlock {
+                text: footer,
+                font: &self.config.font,
+                font_size: font_size * 10 / 12,
+                alignment: Alignment::Center,
+                boxed: false,
+            });
+        }
+        blocks
+    }
+
+    /// The height reserved for the decorations above the participants.
+    pub fn top_height(&self) -> u32 {
+        stacked_height(&self.top_blocks())
+    }
+
+    /// The height reserved for the decorations below the participants.
+    pub fn bottom_height(&self) -> u32 {
+        stacked_height(&self.bottom_blocks())
+    }
+
+    /// The minimum width of the diagram for the decorations to fit.
+    pub fn width(&self) -> u32 {
+        self.top_blocks()
+            .iter()
+            .chain(self.bottom_blocks().iter())
+            .map(|block| block.width() + DECORATION_MARGIN * 2)
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn draw(&self, renderer: &mut dyn Renderer, grid: &GridSize) {
+        let mut y = 0;
+        for block in self.top_blocks() {
+            block.draw(renderer, y, grid.width());
+            y += block.height() + DECORATION_MARGIN;
+        }
+
+        let mut y = grid.timeline_bottom() + DECORATION_MARGIN;
+        for block in self.bottom_blocks() {
+            block.draw(renderer, y, grid.width());
+            y += block.height() + DECORATION_MARGIN;
+        }
+    }
+}
+
+fn stacked_height(blocks: &[TextBlock]) -> u32 {
+    blocks
+        .iter()
+        .map(|block| block.height() + DECORATION_MARGIN)
+        .sum()
+}
+
+#[derive(Clone)]
+pub struct DecorationConfig {
+    pub font_size: u32,
+    pub font: Font,
+}
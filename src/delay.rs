@@ -0,0 +1,60 @@
+use crate::diagram::{SequenceDiagram, TimelineEvent};
+use crate::rendering::font::Font;
+use crate::rendering::layout::{string_width, GridSize, ReservedWidth};
+use crate::rendering::renderer::Renderer;
+
+/// A delay in the diagram, across which the lifelines are drawn dotted.
+pub struct Delay {
+    label: Option<String>,
+    config: DelayConfig,
+}
+
+impl Delay {
+    pub(crate) fn new(label: Option<String>, config: DelayConfig) -> Delay {
+        Delay { label, config }
+    }
+}
+
+impl TimelineEvent for Delay {
+    fn draw(&self, _: &SequenceDiagram, renderer: &mut dyn Renderer, grid: &GridSize, row: usize) {
+        if let Some(label) = &self.label {
+            let y = grid.get_row_center(row) - self.config.font_size / 2;
+            renderer.render_text(
+                label,
+                grid.width() / 2,
+                y,
+                &self.config.font,
+                self.config.font_size,
+                "middle",
+            );
+        }
+    }
+
+    fn reserved_width(&self) -> Option<ReservedWidth> {
+        self.label.as_ref().map(|label| {
+            let width = string_width(label, &self.config.font, self.config.font_size) + 20;
+            ReservedWidth::new(0, usize::MAX, width)
+        })
+    }
+
+    fn height(&self) -> u32 {
+        match self.label {
+            Some(_) => self.config.font_size * 2,
+            None => self.config.font_size * 3 / 2,
+        }
+    }
+
+    fn col_range(&self) -> Option<(usize, usize)> {
+        None
+    }
+
+    fn is_delay(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DelayConfig {
+    pub font_size: u32,
+    pub font: Font,
+}
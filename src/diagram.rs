@@ -1,12 +1,16 @@
 use crate::config::Config;
+use crate::decoration::Decorations;
+use crate::delay::Delay;
 use crate::error::Error;
 use crate::group::{AltElse, Group, GroupEnded, GroupStarted};
-use crate::message::{Message, MessageSent};
-use crate::note::{Note, NoteOrientation};
+use crate::message::{Message, MessageEnd, MessageSent};
+use crate::note::{Note, NoteOrientation, NoteShape};
 use crate::participant::{Participant, ParticipantCreated, ParticipantKind};
+use crate::participant_box::ParticipantBox;
 use crate::rendering::layout::{GridSize, ReservedWidth};
-use crate::rendering::renderer::{LineStyle, Renderer};
+use crate::rendering::renderer::{Arrow, Renderer};
 use crate::separator::Separator;
+use crate::spacer::Spacer;
 use std::cell::RefCell;
 use std::rc::Rc;
 
@@ -36,11 +40,37 @@ pub trait TimelineEvent {
     /// The column indices the event relates to. It's used in groups to determine which
     /// columns the group needs to wrap.
     fn col_range(&self) -> Option<(usize, usize)>;
+
+    /// The space the event takes up below the bottom of its row, e.g. for a label drawn
+    /// under an arrow.
+    fn height_below(&self) -> u32 {
+        0
+    }
+
+    /// Returns the participant if the event draws its head and lifeline.
+    fn drawn_participant(&self) -> Option<&Rc<RefCell<Participant>>> {
+        None
+    }
+
+    /// Returns the message if the event draws one.
+    fn message(&self) -> Option<&Message> {
+        None
+    }
+
+    /// Returns true if the event is a delay, across which the lifelines are drawn dotted.
+    fn is_delay(&self) -> bool {
+        false
+    }
 }
 
 pub struct SequenceDiagram {
     participants: Vec<Rc<RefCell<Participant>>>,
     timeline: Vec<Vec<Box<dyn TimelineEvent>>>,
+    pending_creations: Vec<Rc<RefCell<Participant>>>,
+    boxes: Vec<ParticipantBox>,
+    decorations: Decorations,
+    warnings: Vec<String>,
+    footbox: bool,
     config: Config,
 }
 
@@ -49,6 +79,11 @@ impl SequenceDiagram {
         SequenceDiagram {
             participants: vec![],
             timeline: vec![vec![]],
+            pending_creations: vec![],
+            boxes: vec![],
+            decorations: Decorations::new(config.decoration_config.clone()),
+            warnings: vec![],
+            footbox: true,
             config,
         }
     }
@@ -62,16 +97,130 @@ impl SequenceDiagram {
         &self.participants
     }
 
+    /// Returns true if the participants are drawn a second time at the bottom.
+    pub fn shows_footbox(&self) -> bool {
+        self.footbox
+    }
+
+    pub fn set_footbox(&mut self, footbox: bool) {
+        self.footbox = footbox;
+    }
+
+    /// Removes the participants that neither take part in a message nor have a note
+    /// attached, along with the boxes they leave empty. The remaining participants are
+    /// renumbered to keep their columns contiguous.
+    pub fn remove_unlinked_participants(&mut self) {
+        let is_linked = |p: &Rc<RefCell<Participant>>| p.borrow().is_linked();
+        if self.participants.iter().all(is_linked) {
+            return;
+        }
+
+        self.timeline[0].retain(|ev| ev.drawn_participant().is_none_or(is_linked));
+
+        // The new index of each participant, or None if it's removed
+        let mut new_indices = vec![];
+        let mut next_idx = 0;
+        for participant in &self.participants {
+            if is_linked(participant) {
+                new_indices.push(Some(next_idx));
+                next_idx += 1;
+            } else {
+                new_indices.push(None);
+            }
+        }
+        self.reindex_participants(&new_indices);
+    }
+
+    /// Sorts the participants by their order, keeping the participants with the same order
+    /// in the order they were declared in.
+    pub fn sort_participants(&mut self) {
+        let mut sorted: Vec<usize> = (0..self.participants.len()).collect();
+        sorted.sort_by_key(|&idx| self.participants[idx].borrow().get_order());
+
+        let mut new_indices = vec![None; sorted.len()];
+        for (new_idx, old_idx) in sorted.into_iter().enumerate() {
+            new_indices[old_idx] = Some(new_idx);
+        }
+        self.reindex_participants(&new_indices);
+    }
+
+    /// Moves each participant to its new index, or removes it if it doesn't have one, and
+    /// updates the boxes to span the new indices of their participants.
+    fn reindex_participants(&mut self, new_indices: &[Option<usize>]) {
+        self.boxes.retain_mut(|participant_box| {
+            let remaining = new_indices[participant_box.first..=participant_box.last]
+                .iter()
+                .flatten();
+            match (remaining.clone().min(), remaining.max()) {
+                (Some(&first), Some(&last)) => {
+                    participant_box.first = first;
+                    participant_box.last = last;
+                    true
+                }
+                _ => false,
+            }
+        });
+
+        let mut participants: Vec<_> = self
+            .participants
+            .drain(..)
+            .zip(new_indices)
+            .filter_map(|(participant, new_idx)| new_idx.map(|idx| (idx, participant)))
+            .collect();
+        participants.sort_by_key(|(idx, _)| *idx);
+        for (idx, participant) in &participants {
+            participant.borrow_mut().idx = *idx;
+        }
+        self.participants = participants
+            .into_iter()
+            .map(|(_, participant)| participant)
+            .collect();
+    }
+
+    /// Returns the warnings about parts of the diagram that were ignored.
+    pub fn get_warnings(&self) -> &Vec<String> {
+        &self.warnings
+    }
+
+    pub fn add_warning(&mut self, warning: String) {
+        self.warnings.push(warning);
+    }
+
+    /// Returns the boxes grouping the participants.
+    pub fn get_boxes(&self) -> &Vec<ParticipantBox> {
+        &self.boxes
+    }
+
+    pub fn add_box(&mut self, participant_box: ParticipantBox) {
+        self.boxes.push(participant_box);
+    }
+
+    /// Returns the title, header, footer, caption and legend of the diagram.
+    pub fn get_decorations(&self) -> &Decorations {
+        &self.decorations
+    }
+
+    pub fn get_decorations_mut(&mut self) -> &mut Decorations {
+        &mut self.decorations
+    }
+
     pub fn get_timeline(&self) -> &Vec<Vec<Box<dyn TimelineEvent>>> {
         &self.timeline
     }
 
     /// Returns the participant for the supplied participant ID or returns None if
-    /// there isn't a participant with the ID.
+    /// there isn't a participant with the ID. Participants declared with an alias can also
+    /// be referenced by their label, e.g. `"Payment Gateway"` for
+    /// `participant "Payment Gateway" as PG`.
     pub fn find_participant_by_name(&self, id: &str) -> Option<Rc<RefCell<Participant>>> {
         self.participants
             .iter()
             .find(|&p| p.borrow().name.as_str() == id)
+            .or_else(|| {
+                self.participants
+                    .iter()
+                    .find(|&p| p.borrow().get_label().as_str() == id)
+            })
             .cloned()
     }
 
@@ -85,47 +234,118 @@ impl SequenceDiagram {
         rc_participant
     }
 
-    fn get_or_create_participant(&mut self, name: &str) -> Rc<RefCell<Participant>> {
-        self.find_participant_by_name(&name).unwrap_or_else(|| {
+    /// Adds a participant that is created mid-diagram. The participant is drawn at the row
+    /// of the first message it takes part in.
+    pub fn add_created_participant(
+        &mut self,
+        mut participant: Participant,
+    ) -> Result<Rc<RefCell<Participant>>, Error> {
+        if self.find_participant_by_name(&participant.name).is_some() {
+            return Err(Error::new(format!(
+                "Creating participant that already exists: {}",
+                participant.name
+            )));
+        }
+        participant.idx = self.participants.len();
+        let rc_participant = Rc::new(RefCell::new(participant));
+        self.participants.push(rc_participant.clone());
+        self.pending_creations.push(rc_participant.clone());
+        Ok(rc_participant)
+    }
+
+    /// Returns the participants that have been created but haven't taken part in any
+    /// messages yet.
+    pub fn get_pending_creations(&self) -> &Vec<Rc<RefCell<Participant>>> {
+        &self.pending_creations
+    }
+
+    pub fn get_or_create_participant(&mut self, name: &str) -> Rc<RefCell<Participant>> {
+        self.find_participant_by_name(name).unwrap_or_else(|| {
             let p = Participant::new(
                 name.to_string(),
                 ParticipantKind::Default,
-                self.config.participant_config,
+                self.config.participant_config.clone(),
             );
             self.add_participant(p)
         })
     }
 
+    /// Adds a message to the timeline. If `new_row` is false, the message is drawn on the
+    /// last row in parallel with its events, unless it overlaps a message on that row.
     pub fn add_message(
         &mut self,
-        from: &str,
-        to: &str,
+        from: MessageEnd,
+        to: MessageEnd,
         label: String,
-        style: LineStyle,
+        number: Option<String>,
+        arrow: Arrow,
+        new_row: bool,
     ) -> Message {
-        let from_participant = self.get_or_create_participant(&from);
-        let to_participant = self.get_or_create_participant(&to);
-
         let message = Message {
-            from: from_participant,
-            to: to_participant,
+            from,
+            to,
             label,
-            style,
-            config: self.config.message_config,
+            number,
+            arrow,
+            config: self.config.message_config.clone(),
         };
-        self.timeline.push(vec![Box::new(MessageSent {
+        let overlapping = !new_row
+            && self.timeline.last().is_some_and(|events| {
+                events
+                    .iter()
+                    .filter_map(|event| event.message())
+                    .any(|m| m.overlaps(&message))
+            });
+        if overlapping {
+            self.add_warning(format!(
+                "Parallel message overlaps another message on its row: {}",
+                message.label
+            ));
+        }
+
+        let event = Box::new(MessageSent {
             message: message.clone(),
-        })]);
+        });
+        if new_row || overlapping {
+            self.timeline.push(vec![event]);
+        } else {
+            self.timeline.last_mut().unwrap().push(event);
+        }
+
+        let row = self.timeline.len() - 1;
+        for end in &[&message.from, &message.to] {
+            if let MessageEnd::Participant(participant) = end {
+                participant.borrow_mut().link();
+                self.place_pending_creation(participant, row);
+            }
+        }
         message
     }
 
-    pub fn activate(&mut self, participant_name: &str, start: Option<usize>) {
-        let participant = self.get_or_create_participant(&participant_name);
-        participant.borrow_mut().activate(start);
+    fn place_pending_creation(&mut self, participant: &Rc<RefCell<Participant>>, row: usize) {
+        if let Some(pos) = self
+            .pending_creations
+            .iter()
+            .position(|p| Rc::ptr_eq(p, participant))
+        {
+            let participant = self.pending_creations.remove(pos);
+            participant.borrow_mut().create(row);
+            self.timeline[row].push(Box::new(ParticipantCreated { participant }));
+        }
+    }
+
+    pub fn activate(
+        &mut self,
+        participant_name: &str,
+        start: Option<usize>,
+        colour: Option<String>,
+    ) {
+        let participant = self.get_or_create_participant(participant_name);
+        participant.borrow_mut().activate(start, colour);
     }
 
     pub fn deactivate(&mut self, participant_name: &str) -> Result<(), Error> {
-        match self.find_participant_by_name(&participant_name) {
+        match self.find_participant_by_name(participant_name) {
             Some(participant) => {
                 if !participant
                     .borrow_mut()
@@ -147,6 +367,25 @@ impl SequenceDiagram {
         Ok(())
     }
 
+    pub fn destroy(&mut self, participant_name: &str, row: usize) -> Result<(), Error> {
+        match self.find_participant_by_name(participant_name) {
+            Some(participant) => {
+                if participant.borrow().is_destroyed() {
+                    return Err(Error::new(format!(
+                        "Destroying participant more than once: {}",
+                        participant_name
+                    )));
+                }
+                participant.borrow_mut().destroy(row);
+                Ok(())
+            }
+            None => Err(Error::new(format!(
+                "Missing participant for destroy: {}",
+                participant_name
+            ))),
+        }
+    }
+
     pub fn start_group(&mut self, group: Rc<RefCell<Group>>) {
         self.timeline.push(vec![Box::new(GroupStarted { group })]);
     }
@@ -156,15 +395,33 @@ impl SequenceDiagram {
         self.timeline.push(vec![Box::new(GroupEnded)]);
     }
 
-    pub fn add_alt_case(&mut self, group: Rc<RefCell<Group>>) {
-        self.timeline.push(vec![Box::new(AltElse { group })]);
+    pub fn add_alt_case(&mut self, group: Rc<RefCell<Group>>, case: usize) {
+        self.timeline.push(vec![Box::new(AltElse { group, case })]);
     }
 
-    pub fn add_note(&mut self, label: String, orientation: NoteOrientation, new_row: bool) {
+    pub fn add_note(
+        &mut self,
+        label: String,
+        orientation: NoteOrientation,
+        shape: NoteShape,
+        colour: Option<String>,
+        new_row: bool,
+    ) {
+        let participants = match &orientation {
+            NoteOrientation::LeftOf(p) | NoteOrientation::RightOf(p) => vec![p.clone()],
+            NoteOrientation::Over(participants) => participants.clone(),
+            NoteOrientation::Across => vec![],
+        };
+        for participant in participants {
+            participant.borrow_mut().link();
+        }
+
         let event = Box::new(Note {
             label,
             orientation,
-            config: self.config.note_config,
+            shape,
+            fill: colour.unwrap_or_else(|| self.config.note_config.background.clone()),
+            config: self.config.note_config.clone(),
         });
         if new_row {
             self.timeline.push(vec![event]);
@@ -173,8 +430,17 @@ impl SequenceDiagram {
         }
     }
 
+    pub fn add_delay(&mut self, label: Option<String>) {
+        let delay = Delay::new(label, self.config.delay_config.clone());
+        self.timeline.push(vec![Box::new(delay)]);
+    }
+
+    pub fn add_spacer(&mut self, height: Option<u32>) {
+        self.timeline.push(vec![Box::new(Spacer::new(height))]);
+    }
+
     pub fn add_separator(&mut self, label: String) {
-        let separator = Separator::new(label, self.config.separator_config);
+        let separator = Separator::new(label, self.config.separator_config.clone());
         self.timeline.push(vec![Box::new(separator)]);
     }
 }
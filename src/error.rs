@@ -3,20 +3,20 @@ use pest::error::Error as PestError;
 
 #[derive(Debug)]
 pub enum Error {
-    PestError(PestError<Rule>),
+    PestError(Box<PestError<Rule>>),
     ModelError { message: String },
 }
 
 impl From<PestError<Rule>> for Error {
     fn from(err: PestError<Rule>) -> Self {
-        Error::PestError(err)
+        Error::PestError(Box::new(err))
     }
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            Error::PestError(err) => write!(f, "{}", err.to_string()),
+            Error::PestError(err) => write!(f, "{}", err),
             Error::ModelError { message } => write!(f, "{}", message),
         }
     }
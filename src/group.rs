@@ -1,6 +1,7 @@
 use crate::diagram::{SequenceDiagram, TimelineEvent};
-use crate::rendering::layout::{string_width, GridSize, ReservedWidth};
-use crate::rendering::renderer::{RectParams, Renderer, LIGHT_PURPLE, MEDIUM_PURPLE};
+use crate::rendering::font::Font;
+use crate::rendering::layout::{text_height, text_width, GridSize, ReservedWidth};
+use crate::rendering::renderer::{RectParams, Renderer};
 
 use nalgebra::Point2;
 use std::cell::RefCell;
@@ -32,20 +33,86 @@ pub struct AltGroup {
 pub enum Group {
     SimpleGroup(SimpleGroup),
     AltGroup(AltGroup),
+    Loop(SimpleGroup),
+    Opt(SimpleGroup),
+    Par(AltGroup),
+    Break(SimpleGroup),
+    Critical(SimpleGroup),
+    Neg(SimpleGroup),
 }
 
 impl Group {
+    /// Creates the group for the supplied group keyword, e.g. `loop` or `alt`.
+    /// Returns None if the keyword does not denote a known group type.
+    pub fn from_keyword(
+        keyword: &str,
+        start: usize,
+        header: String,
+        config: GroupConfig,
+    ) -> Option<Group> {
+        let keyword_group =
+            || SimpleGroup::new(start, keyword.to_string(), header.clone(), config.clone());
+        Some(match keyword {
+            "group" => Group::SimpleGroup(SimpleGroup::new(start, header, "".to_string(), config)),
+            "alt" => Group::AltGroup(AltGroup::new(start, keyword.to_string(), header, config)),
+            "par" => Group::Par(AltGroup::new(start, keyword.to_string(), header, config)),
+            "loop" => Group::Loop(keyword_group()),
+            "opt" => Group::Opt(keyword_group()),
+            "break" => Group::Break(keyword_group()),
+            "critical" => Group::Critical(keyword_group()),
+            "neg" => Group::Neg(keyword_group()),
+            _ => return None,
+        })
+    }
+
     pub fn end(&mut self, end: usize) {
         match self {
-            Group::SimpleGroup(g) => g.end(end),
-            Group::AltGroup(g) => g.end(end),
+            Group::AltGroup(g) | Group::Par(g) => g.end(end),
+            Group::SimpleGroup(g)
+            | Group::Loop(g)
+            | Group::Opt(g)
+            | Group::Break(g)
+            | Group::Critical(g)
+            | Group::Neg(g) => g.end(end),
+        }
+    }
+
+    pub fn get_simple_group(&self) -> &SimpleGroup {
+        match self {
+            Group::AltGroup(g) | Group::Par(g) => g.get_simple_group(),
+            Group::SimpleGroup(g)
+            | Group::Loop(g)
+            | Group::Opt(g)
+            | Group::Break(g)
+            | Group::Critical(g)
+            | Group::Neg(g) => g,
         }
     }
 
-    fn config(&self) -> GroupConfig {
+    /// Returns the group with `else` branches or None if the group doesn't support them.
+    pub fn as_multi_case_mut(&mut self) -> Option<&mut AltGroup> {
         match self {
-            Group::SimpleGroup(g) => g.config,
-            Group::AltGroup(g) => g.get_simple_group().config,
+            Group::AltGroup(g) | Group::Par(g) => Some(g),
+            _ => None,
+        }
+    }
+
+    /// The height of the row with the group's label and header.
+    fn header_height(&self) -> u32 {
+        let group = self.get_simple_group();
+        let font_size = group.config.font_size;
+        text_height(&group.label, font_size).max(text_height(&group.header, font_size))
+            + font_size / 4
+    }
+
+    /// The height of the row with the label of the `else` branch with the given index.
+    fn case_height(&self, case: usize) -> u32 {
+        let font_size = self.get_simple_group().config.font_size;
+        match self {
+            Group::AltGroup(g) | Group::Par(g) => {
+                text_height(&g.cases[case].label, font_size) + font_size / 4
+            }
+            _ => font_size * 5 / 4,
         }
     }
 }
@@ -83,11 +150,11 @@ impl SimpleGroup {
 }
 
 impl AltGroup {
-    pub fn new(start: usize, header: String, config: GroupConfig) -> AltGroup {
+    pub fn new(start: usize, label: String, header: String, config: GroupConfig) -> AltGroup {
         let group = SimpleGroup {
             start,
             end: 0,
-            label: "alt".to_string(),
+            label,
             header,
             config,
         };
@@ -124,6 +191,8 @@ pub struct GroupEnded;
 
 pub struct AltElse {
     pub(crate) group: Rc<RefCell<Group>>,
+    /// The index of the case in the group
+    pub(crate) case: usize,
 }
 
 impl TimelineEvent for GroupStarted {
@@ -142,7 +211,7 @@ impl TimelineEvent for GroupStarted {
     }
 
     fn height(&self) -> u32 {
-        self.group.borrow().config().font_size * 5 / 4
+        self.group.borrow().header_height()
     }
 
     fn col_range(&self) -> Option<(usize, usize)> {
@@ -192,7 +261,7 @@ impl TimelineEvent for AltElse {
     }
 
     fn height(&self) -> u32 {
-        self.group.borrow().config().font_size * 5 / 4
+        self.group.borrow().case_height(self.case)
     }
 
     fn col_range(&self) -> Option<(usize, usize)> {
@@ -206,11 +275,9 @@ pub fn draw_group(
     diagram: &SequenceDiagram,
     grid_size: &GridSize,
 ) {
-    let simple_group = match group {
-        Group::AltGroup(alt_group) => alt_group.get_simple_group(),
-        Group::SimpleGroup(ref group) => group,
-    };
-    let font_size = simple_group.config.font_size;
+    let simple_group = group.get_simple_group();
+    let config = &simple_group.config;
+    let font_size = config.font_size;
     let y = grid_size.get_row_top(simple_group.get_start());
     let x_pos = calculate_x_pos(simple_group, diagram, grid_size);
     let x = x_pos.0 - 10;
@@ -218,35 +285,44 @@ pub fn draw_group(
     let end_y = grid_size.get_row_bottom(simple_group.get_end());
     let height = end_y - y;
     let rect_params = RectParams {
-        fill: LIGHT_PURPLE,
+        fill: &config.background,
         fill_opacity: 0.2,
-        stroke: MEDIUM_PURPLE,
+        stroke: &config.border,
         stroke_width: 2,
         r: 5,
     };
     renderer.render_rect(x, y, width, height, rect_params);
 
     // Render the label in the top left corner
-    let label_width = string_width(simple_group.get_label(), font_size) + 20;
+    let label = simple_group.get_label();
+    let label_width = text_width(label, &config.font, font_size) + 20;
     let rect_params = RectParams {
-        fill: MEDIUM_PURPLE,
+        fill: &config.border,
         fill_opacity: 1.0,
-        stroke: MEDIUM_PURPLE,
+        stroke: &config.border,
         stroke_width: 2,
         r: 5,
     };
-    renderer.render_rect(x, y, label_width, font_size * 13 / 10, rect_params);
-    renderer.render_text(simple_group.get_label(), x_pos.0, y, font_size, "left");
+    let label_height = text_height(label, font_size) + font_size * 3 / 10;
+    renderer.render_rect(x, y, label_width, label_height, rect_params);
+    renderer.render_text(label, x_pos.0, y, &config.font, font_size, "left");
 
     // Render header to the right of the label
     let header = simple_group.get_header();
     if !header.is_empty() {
         let header = format!("[{}]", header);
-        renderer.render_text(&header, x + label_width + 10, y, font_size, "left");
+        renderer.render_text(
+            &header,
+            x + label_width + 10,
+            y,
+            &config.font,
+            font_size,
+            "left",
+        );
     }
 
-    // If this is an alt group, also render the else blocks
-    if let Group::AltGroup(alt_group) = group {
+    // If this is an alt or par group, also render the else blocks
+    if let Group::AltGroup(alt_group) | Group::Par(alt_group) = group {
         for case in alt_group.get_cases() {
             let y = grid_size.get_row_top(case.row);
             renderer.render_line(
@@ -254,10 +330,17 @@ pub fn draw_group(
                 Point2::new(x + width, y),
                 2,
                 10,
-                MEDIUM_PURPLE,
+                &config.border,
                 None,
             );
-            renderer.render_text(&format!("[{}]", &case.label), x_pos.0, y, font_size, "left");
+            renderer.render_text(
+                &format!("[{}]", &case.label),
+                x_pos.0,
+                y,
+                &config.font,
+                font_size,
+                "left",
+            );
         }
     }
 }
@@ -277,13 +360,18 @@ fn calculate_x_pos(
         }
     }
 
-    let min_col = grid_size.cols[cols.iter().min().unwrap() + 1];
-    let max_col = grid_size.cols[cols.iter().max().unwrap() + 1];
-
-    (min_col, max_col)
+    // A group without any messages or notes spans the whole diagram, less the margin
+    // added around it when drawing
+    match (cols.iter().min(), cols.iter().max()) {
+        (Some(min), Some(max)) => (grid_size.cols[min + 1], grid_size.cols[max + 1]),
+        _ => (10, grid_size.width().saturating_sub(10).max(10)),
+    }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub struct GroupConfig {
     pub font_size: u32,
+    pub font: Font,
+    pub background: String,
+    pub border: String,
 }
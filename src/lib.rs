@@ -2,7 +2,10 @@ extern crate pest;
 #[macro_use]
 extern crate pest_derive;
 
+mod autonumber;
 mod config;
+mod decoration;
+mod delay;
 mod diagram;
 mod error;
 mod group;
@@ -10,8 +13,11 @@ mod message;
 mod note;
 mod parser;
 mod participant;
+mod participant_box;
 mod rendering;
 mod separator;
+mod skinparam;
+mod spacer;
 
 /// Parses the supplied diagram string into SVG string.
 ///
@@ -20,9 +26,24 @@ mod separator;
 /// * `content` - A string representing the diagram in the diagram DSL
 /// * `show_debug_lines` - A boolean to enable debug lines for the layout in the SVG
 pub fn parse(content: &str, show_debug_lines: bool) -> Result<String, error::Error> {
+    parse_with_warnings(content, show_debug_lines).map(|(svg, _)| svg)
+}
+
+/// Parses the supplied diagram string into SVG string like `parse`, also returning the
+/// warnings about the parts of the diagram that were ignored, e.g. unknown skinparams.
+///
+/// # Arguments
+///
+/// * `content` - A string representing the diagram in the diagram DSL
+/// * `show_debug_lines` - A boolean to enable debug lines for the layout in the SVG
+pub fn parse_with_warnings(
+    content: &str,
+    show_debug_lines: bool,
+) -> Result<(String, Vec<String>), error::Error> {
     let config = config::Config {
         ..Default::default()
     };
     let diagram = parser::create_diagram(content, config)?;
-    Ok(rendering::render(&diagram, show_debug_lines))
+    let svg = rendering::render(&diagram, show_debug_lines);
+    Ok((svg, diagram.get_warnings().clone()))
 }
@@ -1,25 +1,190 @@
 use crate::diagram::{SequenceDiagram, TimelineEvent};
 use crate::participant::Participant;
-use crate::rendering::layout::{string_width, GridSize, ReservedWidth};
-use crate::rendering::renderer::{LineStyle, Renderer};
+use crate::rendering::font::Font;
+use crate::rendering::layout::{text_height, text_width, wrap_text, GridSize, ReservedWidth};
+use crate::rendering::renderer::{Arrow, ArrowEnd, ArrowHead, LineStyle, Renderer};
 use nalgebra::Point2;
 use std::cell::RefCell;
 use std::rc::Rc;
 
+/// The edge of the diagram a message enters from or leaves to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Border {
+    Left,
+    Right,
+    /// A short stub to the left of the participant rather than the diagram edge
+    ShortLeft,
+    /// A short stub to the right of the participant rather than the diagram edge
+    ShortRight,
+}
+
+impl Border {
+    pub fn is_left(self) -> bool {
+        self == Border::Left || self == Border::ShortLeft
+    }
+
+    /// Returns the x coordinate of the border for a message to or from the supplied
+    /// position on the participant's lifeline.
+    fn x(self, lifeline_x: u32, grid_size: &GridSize) -> u32 {
+        match self {
+            Border::Left => grid_size.cols[0] + BORDER_MARGIN,
+            Border::Right => grid_size.width() - BORDER_MARGIN,
+            Border::ShortLeft => lifeline_x.saturating_sub(BORDER_STUB_LENGTH),
+            Border::ShortRight => lifeline_x + BORDER_STUB_LENGTH,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MessageEnd {
+    Participant(Rc<RefCell<Participant>>),
+    Border(Border),
+}
+
+impl MessageEnd {
+    /// Returns the layout column of the message end. Borders map to the outermost columns.
+    fn layout_col(&self) -> usize {
+        match self {
+            MessageEnd::Participant(p) => p.borrow().get_idx() + 1,
+            MessageEnd::Border(border) if border.is_left() => 0,
+            MessageEnd::Border(_) => usize::MAX,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Message {
-    pub from: Rc<RefCell<Participant>>,
-    pub to: Rc<RefCell<Participant>>,
+    pub from: MessageEnd,
+    pub to: MessageEnd,
     pub label: String,
-    pub style: LineStyle,
+    pub number: Option<String>,
+    pub arrow: Arrow,
     pub config: MessageConfig,
 }
 
+impl Message {
+    /// Returns the label prefixed with the message's number if it has one.
+    pub fn numbered_label(&self) -> String {
+        match &self.number {
+            Some(number) if self.label.is_empty() => number.clone(),
+            Some(number) => format!("{} {}", number, self.label),
+            None => self.label.clone(),
+        }
+    }
+
+    /// Returns the numbered label, wrapped if the config limits the width of labels.
+    pub fn display_label(&self) -> String {
+        let label = self.numbered_label();
+        match self.config.max_message_width {
+            Some(max_width) => {
+                wrap_text(&label, max_width, &self.config.font, self.config.font_size)
+            }
+            None => label,
+        }
+    }
+
+    fn label_height(&self) -> u32 {
+        text_height(&self.display_label(), self.config.font_size)
+    }
+
+    fn has_label(&self) -> bool {
+        !self.label.is_empty() || self.number.is_some()
+    }
+
+    /// Returns true if the label is drawn below the arrow rather than above it, which the
+    /// config can request for replies.
+    fn label_below_arrow(&self) -> bool {
+        self.config.response_below_arrow && self.arrow.style == LineStyle::Dashed
+    }
+
+    /// Returns the arrow as drawn. Strict UML requires open arrow heads on replies.
+    fn displayed_arrow(&self) -> Arrow {
+        if !self.config.strict_uml || self.arrow.style != LineStyle::Dashed {
+            return self.arrow.clone();
+        }
+        let open = |end: ArrowEnd| ArrowEnd {
+            head: match end.head {
+                ArrowHead::Filled => ArrowHead::Thin,
+                ArrowHead::HalfUpper => ArrowHead::ThinHalfUpper,
+                ArrowHead::HalfLower => ArrowHead::ThinHalfLower,
+                head => head,
+            },
+            ..end
+        };
+        Arrow {
+            start: open(self.arrow.start),
+            end: open(self.arrow.end),
+            ..self.arrow.clone()
+        }
+    }
+
+    /// Returns the border of the diagram the message is sent from or to, if any.
+    pub fn border(&self) -> Option<Border> {
+        match (&self.from, &self.to) {
+            (MessageEnd::Border(border), _) | (_, MessageEnd::Border(border)) => Some(*border),
+            _ => None,
+        }
+    }
+
+    pub fn is_self_message(&self) -> bool {
+        self.from == self.to
+    }
+
+    /// Returns true if the messages would be drawn over each other on the same row. Messages
+    /// that only meet at a lifeline don't overlap, while self messages take up the space to
+    /// the right of their participant.
+    pub fn overlaps(&self, other: &Message) -> bool {
+        let (left, right) = self.span();
+        let (other_left, other_right) = other.span();
+        match (self.is_self_message(), other.is_self_message()) {
+            (true, true) => left == other_left,
+            (true, false) => other_left <= left && left < other_right,
+            (false, true) => left <= other_left && other_left < right,
+            (false, false) => left < other_right && other_left < right,
+        }
+    }
+
+    /// Returns the positions of the leftmost and the rightmost end of the message. Positions
+    /// are compared the way participants are sorted, by their order and then their index.
+    fn span(&self) -> ((i32, usize), (i32, usize)) {
+        let position = |end: &MessageEnd| match end {
+            MessageEnd::Participant(p) => (p.borrow().get_order(), p.borrow().get_idx()),
+            MessageEnd::Border(border) if border.is_left() => (i32::MIN, 0),
+            MessageEnd::Border(_) => (i32::MAX, usize::MAX),
+        };
+        let (from, to) = (position(&self.from), position(&self.to));
+        (from.min(to), from.max(to))
+    }
+
+    /// Returns the leftmost and the rightmost participant the message is sent between.
+    /// For messages to or from the border both are the same participant.
+    pub fn participant_bounds(&self) -> (Rc<RefCell<Participant>>, Rc<RefCell<Participant>>) {
+        match (&self.from, &self.to) {
+            (MessageEnd::Participant(from), MessageEnd::Participant(to)) => {
+                if from < to {
+                    (from.clone(), to.clone())
+                } else {
+                    (to.clone(), from.clone())
+                }
+            }
+            (MessageEnd::Participant(p), MessageEnd::Border(_))
+            | (MessageEnd::Border(_), MessageEnd::Participant(p)) => (p.clone(), p.clone()),
+            (MessageEnd::Border(_), MessageEnd::Border(_)) => {
+                panic!("Message with no participant")
+            }
+        }
+    }
+}
+
 pub struct MessageSent {
     pub(crate) message: Message,
 }
 
 pub const ARROW_DISTANCE_FROM_BOTTOM: u32 = 10;
+pub const BORDER_STUB_LENGTH: u32 = 40;
+/// The space between the edge of the diagram and messages to or from the border, leaving
+/// room for the circles on the arrows' ends
+pub const BORDER_MARGIN: u32 = 10;
 
 impl TimelineEvent for MessageSent {
     fn draw(
@@ -32,44 +197,64 @@ impl TimelineEvent for MessageSent {
         draw_message(renderer, &self.message, row, grid);
     }
 
+    fn message(&self) -> Option<&Message> {
+        Some(&self.message)
+    }
+
     fn reserved_width(&self) -> Option<ReservedWidth> {
-        let from_idx = self.message.from.borrow().get_idx();
-        let mut to_idx = self.message.to.borrow().get_idx();
-        if from_idx == to_idx {
-            to_idx += 1;
+        let from_col = self.message.from.layout_col();
+        let mut to_col = self.message.to.layout_col();
+        if self.message.is_self_message() {
+            to_col += 1;
         }
 
-        // The width of the message depends on its label plus some constant margin
-        let width = string_width(&self.message.label, self.message.config.font_size) + 40;
-        Some(ReservedWidth::new(from_idx + 1, to_idx + 1, width))
+        // The width of the message depends on its longest line plus some constant margin
+        let label = self.message.display_label();
+        let width = text_width(
+            &label,
+            &self.message.config.font,
+            self.message.config.font_size,
+        ) + 40;
+        Some(ReservedWidth::new(from_col, to_col, width))
     }
 
     fn height(&self) -> u32 {
-        if self.message.from != self.message.to {
-            if self.message.label.is_empty() {
-                // Regular messages with no label don't need as much vertical space
+        let message = &self.message;
+        if !message.is_self_message() {
+            if !message.has_label() || message.label_below_arrow() {
+                // Regular messages with no label above the arrow don't need as much space
                 20
             } else {
-                20 + self.message.config.font_size
+                20 + message.label_height()
             }
         } else {
-            55
+            55.max(message.label_height() + 30)
         }
     }
 
-    fn col_range(&self) -> Option<(usize, usize)> {
-        let from_idx = self.message.from.borrow().get_idx();
-        let to_idx = self.message.to.borrow().get_idx();
-        Some(if from_idx < to_idx {
-            (from_idx, to_idx)
+    fn height_below(&self) -> u32 {
+        let message = &self.message;
+        if message.has_label() && message.label_below_arrow() && !message.is_self_message() {
+            message.label_height()
         } else {
-            (to_idx, from_idx)
-        })
+            0
+        }
+    }
+
+    fn col_range(&self) -> Option<(usize, usize)> {
+        let (left, right) = self.message.participant_bounds();
+        let left_idx = left.borrow().get_idx();
+        let right_idx = right.borrow().get_idx();
+        Some((left_idx, right_idx))
     }
 }
 
 pub fn draw_message(renderer: &mut dyn Renderer, msg: &Message, row: usize, grid_size: &GridSize) {
-    if msg.from == msg.to {
+    // Hidden messages only take up space
+    if msg.arrow.style == LineStyle::Hidden {
+        return;
+    }
+    if msg.is_self_message() {
         draw_self_message(renderer, msg, row, grid_size);
     } else {
         draw_regular_message(renderer, msg, row, grid_size);
@@ -84,27 +269,29 @@ fn draw_regular_message(
 ) {
     let y = grid_size.get_row_bottom(row) - ARROW_DISTANCE_FROM_BOTTOM;
 
-    let src_idx = msg.from.borrow().get_idx();
-    let dest_idx = msg.to.borrow().get_idx();
-    let (src_offset, dest_offset) = if src_idx < dest_idx {
-        (
-            msg.from.borrow().lifeline_offset(row).1,
-            msg.to.borrow().lifeline_offset(row).0,
-        )
-    } else {
-        (
-            msg.from.borrow().lifeline_offset(row).0,
-            msg.to.borrow().lifeline_offset(row).1,
-        )
-    };
-    let src_x = (grid_size.get_col_center(src_idx) as i32 + src_offset) as u32;
-    let dest_x = (grid_size.get_col_center(dest_idx) as i32 + dest_offset) as u32;
-    let dash = match &msg.style {
-        LineStyle::Plain => 0,
-        LineStyle::Dashed => 5,
+    let (src_x, dest_x) = match (&msg.from, &msg.to) {
+        (MessageEnd::Participant(from), MessageEnd::Participant(to)) => {
+            let to_right = from < to;
+            (
+                lifeline_x(from, to_right, row, grid_size),
+                lifeline_x(to, !to_right, row, grid_size),
+            )
+        }
+        (MessageEnd::Participant(p), MessageEnd::Border(border)) => {
+            let x = lifeline_x(p, !border.is_left(), row, grid_size);
+            (x, border.x(x, grid_size))
+        }
+        (MessageEnd::Border(border), MessageEnd::Participant(p)) => {
+            let x = lifeline_x(p, !border.is_left(), row, grid_size);
+            (border.x(x, grid_size), x)
+        }
+        (MessageEnd::Border(_), MessageEnd::Border(_)) => panic!("Message with no participant"),
     };
-
-    renderer.render_arrow(Point2::new(src_x, y), Point2::new(dest_x, y), dash);
+    renderer.render_arrow(
+        Point2::new(src_x, y),
+        Point2::new(dest_x, y),
+        &msg.displayed_arrow(),
+    );
 
     let text_bounds = if src_x < dest_x {
         (src_x, dest_x)
@@ -112,10 +299,16 @@ fn draw_regular_message(
         (dest_x, src_x)
     };
     let text_x = (text_bounds.1 - text_bounds.0) / 2 + text_bounds.0;
+    let text_y = if msg.label_below_arrow() {
+        y + 3
+    } else {
+        y - msg.label_height() - 5
+    };
     renderer.render_text(
-        &msg.label,
+        &msg.display_label(),
         text_x,
-        y - msg.config.font_size - 5,
+        text_y,
+        &msg.config.font,
         msg.config.font_size,
         "middle",
     );
@@ -125,43 +318,70 @@ fn draw_self_message(renderer: &mut dyn Renderer, msg: &Message, row: usize, gri
     let y = grid_size.get_row_center(row);
     let y_start = y - 20;
     let y_end = grid_size.get_row_bottom(row) - ARROW_DISTANCE_FROM_BOTTOM;
-    let idx = msg.from.borrow().get_idx();
-    let x = grid_size.get_col_center(idx) + msg.from.borrow().lifeline_offset(row).1 as u32;
+    let x = lifeline_x(&msg.participant_bounds().0, true, row, grid_size);
     let x_offset = x + 35;
 
-    let dash = match &msg.style {
-        LineStyle::Plain => 0,
-        LineStyle::Dashed => 5,
-    };
-
-    renderer.render_line(
+    let arrow = &msg.displayed_arrow();
+    renderer.render_arrow(
         Point2::new(x, y_start),
         Point2::new(x_offset, y_start),
-        1,
-        dash,
-        "black",
-        None,
+        &Arrow {
+            end: ArrowEnd::plain(),
+            ..arrow.clone()
+        },
     );
     renderer.render_line(
         Point2::new(x_offset, y_start),
         Point2::new(x_offset, y_end),
-        1,
-        dash,
-        "black",
+        arrow.thickness,
+        arrow.dash(),
+        &arrow.colour,
         None,
     );
-    renderer.render_arrow(Point2::new(x_offset, y_end), Point2::new(x, y_end), dash);
+    renderer.render_arrow(
+        Point2::new(x_offset, y_end),
+        Point2::new(x, y_end),
+        &Arrow {
+            start: ArrowEnd::plain(),
+            ..arrow.clone()
+        },
+    );
 
     renderer.render_text(
-        &msg.label,
+        &msg.display_label(),
         x_offset + 10,
         y_start,
+        &msg.config.font,
         msg.config.font_size,
         "start",
     );
 }
 
-#[derive(Clone, Copy, Debug)]
+/// Returns the x coordinate where a message starts or ends on the participant's lifeline,
+/// taking the activation boxes into account on the requested side.
+fn lifeline_x(
+    participant: &Rc<RefCell<Participant>>,
+    right_side: bool,
+    row: usize,
+    grid_size: &GridSize,
+) -> u32 {
+    let participant = participant.borrow();
+    let offset = if right_side {
+        participant.lifeline_offset(row).1
+    } else {
+        participant.lifeline_offset(row).0
+    };
+    (grid_size.get_col_center(participant.get_idx()) as i32 + offset) as u32
+}
+
+#[derive(Clone, Debug)]
 pub struct MessageConfig {
     pub font_size: u32,
+    pub font: Font,
+    /// The width at which labels are wrapped onto multiple lines
+    pub max_message_width: Option<u32>,
+    /// Whether the labels of dashed reply messages are drawn below the arrow
+    pub response_below_arrow: bool,
+    /// Whether dashed reply messages get open arrow heads as required by UML
+    pub strict_uml: bool,
 }
@@ -1,7 +1,8 @@
 use crate::diagram::{SequenceDiagram, TimelineEvent};
 use crate::participant::Participant;
-use crate::rendering::layout::{string_width, GridSize, ReservedWidth};
-use crate::rendering::renderer::{Renderer, DARK_GREY, LIGHT_GREY};
+use crate::rendering::font::Font;
+use crate::rendering::layout::{text_width, wrap_text, GridSize, ReservedWidth};
+use crate::rendering::renderer::{RectParams, Renderer};
 use std::cell::RefCell;
 use std::rc::Rc;
 
@@ -10,19 +11,45 @@ static PARTICIPANT_MARGIN: u32 = 10;
 pub struct Note {
     pub orientation: NoteOrientation,
     pub label: String,
+    pub shape: NoteShape,
+    pub fill: String,
     pub config: NoteConfig,
 }
 
+#[derive(Clone, Copy, Debug)]
+pub enum NoteShape {
+    Folded,
+    Hexagon,
+    Rectangle,
+}
+
 pub enum NoteOrientation {
     LeftOf(Rc<RefCell<Participant>>),
     RightOf(Rc<RefCell<Participant>>),
     Over(Vec<Rc<RefCell<Participant>>>),
+    Across,
 }
 
 impl Note {
+    /// Returns the label, wrapped if the config limits the width of notes.
+    pub fn display_label(&self) -> String {
+        match self.config.max_note_width {
+            Some(max_width) => wrap_text(
+                &self.label,
+                max_width,
+                &self.config.font,
+                self.config.font_size,
+            ),
+            None => self.label.clone(),
+        }
+    }
+
     fn width(&self) -> u32 {
-        let longest = &self.label.split('\n').max_by_key(|t| t.len()).unwrap();
-        string_width(longest, self.config.font_size)
+        text_width(
+            &self.display_label(),
+            &self.config.font,
+            self.config.font_size,
+        )
     }
 }
 
@@ -34,6 +61,7 @@ impl TimelineEvent for Note {
         grid: &GridSize,
         row: usize,
     ) {
+        let mut box_width = self.width() + PARTICIPANT_MARGIN;
         let x = match &self.orientation {
             NoteOrientation::LeftOf(p) => {
                 grid.get_col_center(p.borrow().get_idx()) - self.width() - PARTICIPANT_MARGIN
@@ -54,25 +82,55 @@ impl TimelineEvent for Note {
                     as u32;
                 unadjusted + PARTICIPANT_MARGIN / 2
             }
+            NoteOrientation::Across => {
+                // The note spans the whole width of the diagram
+                box_width = grid.width() - PARTICIPANT_MARGIN * 2;
+                PARTICIPANT_MARGIN + PARTICIPANT_MARGIN / 2
+            }
         };
         let y = grid.get_row_top(row);
         let box_x = x - PARTICIPANT_MARGIN / 2;
-        renderer.render_note_box(
-            box_x,
+        match self.shape {
+            NoteShape::Folded => renderer.render_note_box(
+                box_x,
+                y,
+                box_width,
+                self.height(),
+                &self.fill,
+                &self.config.border,
+            ),
+            NoteShape::Hexagon => renderer.render_hexagon(
+                box_x,
+                y,
+                box_width,
+                self.height(),
+                &self.fill,
+                &self.config.border,
+            ),
+            NoteShape::Rectangle => {
+                let params = RectParams {
+                    fill: &self.fill,
+                    stroke: &self.config.border,
+                    ..Default::default()
+                };
+                renderer.render_rect(box_x, y, box_width, self.height(), params)
+            }
+        }
+        renderer.render_text(
+            &self.display_label(),
+            x,
             y,
-            self.width() + PARTICIPANT_MARGIN,
-            self.height(),
-            LIGHT_GREY,
-            DARK_GREY,
+            &self.config.font,
+            self.config.font_size,
+            "left",
         );
-        renderer.render_text(&self.label, x, y, self.config.font_size, "left");
     }
 
     fn reserved_width(&self) -> Option<ReservedWidth> {
         let cols = match &self.orientation {
             NoteOrientation::LeftOf(p) => (0, p.borrow().get_idx() + 1),
-            NoteOrientation::RightOf(p) => (p.borrow().get_idx() + 1, usize::max_value()),
-            NoteOrientation::Over(_) => (0, usize::max_value()),
+            NoteOrientation::RightOf(p) => (p.borrow().get_idx() + 1, usize::MAX),
+            NoteOrientation::Over(_) | NoteOrientation::Across => (0, usize::MAX),
         };
         Some(ReservedWidth::new(
             cols.0,
@@ -83,8 +141,8 @@ impl TimelineEvent for Note {
 
     fn height(&self) -> u32 {
         let font_size = self.config.font_size;
-        (font_size as usize * self.label.split('\n').count()) as u32 * 11 / 10
-            + font_size as u32 / 3
+        let lines = self.display_label().split('\n').count() as u32;
+        font_size * lines * 11 / 10 + font_size / 3
     }
 
     fn col_range(&self) -> Option<(usize, usize)> {
@@ -92,7 +150,12 @@ impl TimelineEvent for Note {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub struct NoteConfig {
     pub font_size: u32,
+    pub font: Font,
+    /// The width at which labels are wrapped onto multiple lines
+    pub max_note_width: Option<u32>,
+    pub background: String,
+    pub border: String,
 }
@@ -1,11 +1,15 @@
+use crate::autonumber::Autonumber;
 use crate::config::Config;
+use crate::decoration::{Alignment, Legend};
 use crate::diagram::SequenceDiagram;
 use crate::error::Error;
-use crate::group::{AltGroup, Group, SimpleGroup};
-use crate::message::Message;
-use crate::note::NoteOrientation;
-use crate::participant::{Participant, ParticipantKind};
-use crate::rendering::renderer::LineStyle;
+use crate::group::Group;
+use crate::message::{Border, Message, MessageEnd};
+use crate::note::{NoteOrientation, NoteShape};
+use crate::participant::{Participant, ParticipantKind, Spot, Stereotype};
+use crate::participant_box::ParticipantBox;
+use crate::rendering::renderer::{parse_colour, Arrow, ArrowEnd, ArrowHead, LineStyle};
+use crate::skinparam::apply_skinparam;
 
 use crate::parser::AstNode::ParticipantDefinition;
 use pest::iterators::Pair;
@@ -23,130 +27,333 @@ enum AstNode {
         name: String,
         label: String,
         kind: ParticipantKind,
+        created: bool,
+        order: Option<i32>,
+        stereotype: Option<Stereotype>,
+        colour: Option<String>,
     },
     Message {
-        from: String,
-        to: String,
+        from: MessageParty,
+        to: MessageParty,
         label: String,
-        style: LineStyle,
+        arrow: Arrow,
         activation_modifier: Option<ActivationModifier>,
+        lifecycle_modifier: Option<LifecycleModifier>,
+        /// Whether the message is drawn on the same row as the previous message
+        parallel: bool,
     },
     GroupStart(String, String),
     AltElse(String),
     GroupEnd,
-    Activate(String),
+    Activate(String, Option<String>),
     Deactivate(String),
+    /// Whether messages activate their callee and replies deactivate their sender
+    Autoactivate(bool),
+    Destroy(String),
+    Return(String),
     Note {
         label: String,
         direction: Direction,
+        shape: NoteShape,
+        colour: Option<String>,
     },
     Separator(String),
+    Autonumber(AutonumberCommand),
+    Delay(Option<String>),
+    Spacer(Option<u32>),
+    Decoration(DecorationKind, String),
+    Legend(Alignment, String),
+    BoxStart {
+        title: Option<String>,
+        colour: Option<String>,
+    },
+    BoxEnd,
+    /// Whether the participants are drawn again at the bottom
+    Footbox(bool),
+    /// Whether the participants that don't take part in any message are drawn
+    Unlinked(bool),
+    /// The settings of a skinparam statement as key-value pairs
+    Skinparam(Vec<(String, String)>),
+}
+
+enum DecorationKind {
+    Title,
+    Header,
+    Footer,
+    Caption,
+}
+
+#[derive(Clone)]
+enum MessageParty {
+    Participant(String),
+    Border(Border),
+}
+
+impl MessageParty {
+    /// Returns the name of the participant the `modifier` applies to, which can't be the
+    /// diagram border.
+    fn participant_name(&self, modifier: &str) -> Result<&str, Error> {
+        match self {
+            MessageParty::Participant(name) => Ok(name),
+            MessageParty::Border(_) => Err(Error::new(format!(
+                "{} modifier on the diagram border",
+                modifier
+            ))),
+        }
+    }
+}
+
+/// An activation of a participant, remembering who activated it so that a `return`
+/// can send the reply back.
+struct Call {
+    callee: String,
+    caller: Option<MessageParty>,
+}
+
+/// Removes the innermost activation of the participant from the call stack.
+fn end_call(call_stack: &mut Vec<Call>, callee: &str) {
+    if let Some(pos) = call_stack.iter().rposition(|call| call.callee == callee) {
+        call_stack.remove(pos);
+    }
 }
 
 enum ActivationModifier {
-    Activate,
+    Activate(Option<String>),
     Deactivate,
 }
 
+enum AutonumberCommand {
+    Start {
+        start: Option<Vec<u32>>,
+        step: Option<u32>,
+        format: Option<String>,
+    },
+    Stop,
+    Resume {
+        step: Option<u32>,
+        format: Option<String>,
+    },
+    Increment(usize),
+}
+
+enum LifecycleModifier {
+    Create,
+    Destroy,
+}
+
 enum Direction {
-    Left,
-    Right,
+    Left(Option<String>),
+    Right(Option<String>),
     Over(Vec<String>),
+    Across,
+    OnLink,
 }
 
-pub fn create_diagram(source: &str, config: Config) -> Result<SequenceDiagram, Error> {
+pub fn create_diagram(source: &str, mut config: Config) -> Result<SequenceDiagram, Error> {
+    let ast = parse(source)?;
+    let warnings = apply_skinparams(&mut config, &ast);
     let mut diagram = SequenceDiagram::new(config);
+    for warning in warnings {
+        diagram.add_warning(warning);
+    }
     let mut active_groups: VecDeque<Rc<RefCell<Group>>> = VecDeque::new();
     let mut last_message: Option<(usize, Message)> = None;
-    let ast = parse(source)?;
+    let mut last_sender: Option<MessageParty> = None;
+    let mut call_stack: Vec<Call> = vec![];
+    let mut autonumber = Autonumber::new();
+    let mut open_box: Option<ParticipantBox> = None;
+    let mut show_unlinked = true;
+    let mut autoactivate = false;
 
     for node in ast {
         match node {
-            AstNode::ParticipantDefinition { name, label, kind } => {
-                let p = Participant::with_label(
+            AstNode::ParticipantDefinition {
+                name,
+                label,
+                kind,
+                created,
+                order,
+                stereotype,
+                colour,
+            } => {
+                let mut p = Participant::with_label(
                     name,
                     kind,
                     label,
-                    diagram.get_config().participant_config,
+                    diagram.get_config().participant_config.clone(),
                 );
-                diagram.add_participant(p);
+                if let Some(order) = order {
+                    p.set_order(order);
+                }
+                if let Some(stereotype) = stereotype {
+                    p.set_stereotype(stereotype);
+                }
+                if let Some(colour) = colour {
+                    p.set_fill(colour);
+                }
+                if created {
+                    diagram.add_created_participant(p)?;
+                } else {
+                    diagram.add_participant(p);
+                }
             }
             AstNode::Message {
                 from,
                 to,
                 label,
-                style,
+                arrow,
                 activation_modifier,
+                lifecycle_modifier,
+                parallel,
             } => {
-                let row = diagram.get_timeline().len();
-                let msg = diagram.add_message(&from, &to, label, style);
+                let last_row = diagram.get_timeline().len().checked_sub(1);
+                if parallel && last_message.as_ref().map(|m| m.0) != last_row {
+                    return Err(Error::new(
+                        "Parallel message without a message on the previous row".to_string(),
+                    ));
+                }
+                if let Some(LifecycleModifier::Create) = lifecycle_modifier {
+                    let p = Participant::new(
+                        to.participant_name("Create")?.to_string(),
+                        ParticipantKind::Default,
+                        diagram.get_config().participant_config.clone(),
+                    );
+                    diagram.add_created_participant(p)?;
+                }
+                let number = autonumber.next_number()?;
+                let is_reply = arrow.style == LineStyle::Dashed;
+                // Asynchronous and lost messages don't wait for the callee to return
+                let is_call = !is_reply
+                    && !matches!(
+                        arrow.end.head,
+                        ArrowHead::Thin
+                            | ArrowHead::ThinHalfUpper
+                            | ArrowHead::ThinHalfLower
+                            | ArrowHead::Cross
+                    );
+                let msg = add_message(&mut diagram, &from, &to, label, number, arrow, !parallel)?;
+                let row = diagram.get_timeline().len() - 1;
+                // Without an explicit modifier, auto-activation activates the callee of a
+                // call and deactivates the sender of a reply to an open call
+                let activation_modifier = match (&from, &to) {
+                    _ if activation_modifier.is_some() || !autoactivate => activation_modifier,
+                    (MessageParty::Participant(sender), _) if is_reply => call_stack
+                        .iter()
+                        .any(|call| &call.callee == sender)
+                        .then_some(ActivationModifier::Deactivate),
+                    (_, MessageParty::Participant(_)) if is_call => {
+                        Some(ActivationModifier::Activate(None))
+                    }
+                    _ => None,
+                };
                 if let Some(modifier) = activation_modifier {
                     match modifier {
-                        ActivationModifier::Activate => diagram.activate(&to, Some(row)),
-                        ActivationModifier::Deactivate => diagram.deactivate(&from)?,
+                        ActivationModifier::Activate(colour) => {
+                            let callee = to.participant_name("Activation")?;
+                            diagram.activate(callee, Some(row), colour);
+                            call_stack.push(Call {
+                                callee: callee.to_string(),
+                                caller: Some(from.clone()),
+                            });
+                        }
+                        ActivationModifier::Deactivate => {
+                            let callee = from.participant_name("Activation")?;
+                            diagram.deactivate(callee)?;
+                            end_call(&mut call_stack, callee);
+                        }
                     }
                 }
+                if let Some(LifecycleModifier::Destroy) = lifecycle_modifier {
+                    let name = to.participant_name("Destroy")?;
+                    diagram.destroy(name, row)?;
+                    call_stack.retain(|call| call.callee != name);
+                }
+                last_message = Some((row, msg));
+                last_sender = Some(from);
+            }
+            AstNode::Return(label) => {
+                let call = call_stack
+                    .pop()
+                    .ok_or_else(|| Error::new("return without an activation".to_string()))?;
+                let Call { callee, caller } = call;
+                let caller = caller
+                    .ok_or_else(|| Error::new(format!("return from {} with no caller", callee)))?;
+                let from = MessageParty::Participant(callee);
+                let arrow = Arrow::new(
+                    LineStyle::Dashed,
+                    ArrowEnd::plain(),
+                    ArrowEnd::new(ArrowHead::Filled, false),
+                );
+
+                let row = diagram.get_timeline().len();
+                let number = autonumber.next_number()?;
+                let msg = add_message(&mut diagram, &from, &caller, label, number, arrow, true)?;
+                diagram.deactivate(from.participant_name("Activation")?)?;
                 last_message = Some((row, msg));
+                last_sender = Some(from);
             }
             AstNode::GroupStart(group_type, header) => {
                 let timeline_pos = diagram.get_timeline().len();
-                let config = diagram.get_config().group_config;
-                let group = match group_type.as_str() {
-                    "group" => Group::SimpleGroup(SimpleGroup::new(
-                        timeline_pos,
-                        header,
-                        "".to_string(),
-                        config,
-                    )),
-                    "alt" => Group::AltGroup(AltGroup::new(timeline_pos, header, config)),
-                    _ => return Err(Error::new("Unexpected group type".to_string())),
-                };
+                let config = diagram.get_config().group_config.clone();
+                let group = Group::from_keyword(&group_type, timeline_pos, header, config)
+                    .ok_or_else(|| Error::new("Unexpected group type".to_string()))?;
                 let rc_group = Rc::new(RefCell::new(group));
                 active_groups.push_back(rc_group.clone());
                 diagram.start_group(rc_group);
             }
             AstNode::AltElse(label) => match active_groups.back_mut() {
                 Some(rc_group) => {
-                    match *rc_group.borrow_mut() {
-                        Group::AltGroup(ref mut group) => {
+                    let case = match rc_group.borrow_mut().as_multi_case_mut() {
+                        Some(group) => {
                             let row = diagram.get_timeline().len();
-                            group.add_case(label, row);
+                            group.add_case(label, row)
                         }
-                        _ => {
+                        None => {
                             return Err(Error::new(
-                                "else when active group is not an 'alt' group".to_string(),
+                                "else when active group is not an 'alt' or 'par' group".to_string(),
                             ))
                         }
-                    }
-                    diagram.add_alt_case(rc_group.clone());
+                    };
+                    diagram.add_alt_case(rc_group.clone(), case);
                 }
-                None => return Err(Error::new("else without active alt group".to_string())),
+                None => return Err(Error::new("else without active group".to_string())),
             },
             AstNode::GroupEnd => match active_groups.pop_back() {
                 Some(group) => diagram.end_group(group),
                 None => return Err(Error::new("Found end without active group".to_string())),
             },
-            AstNode::Activate(participant_name) => {
-                diagram.activate(&participant_name, last_message.as_ref().map(|p| p.0));
+            AstNode::Activate(participant_name, colour) => {
+                let start = last_message.as_ref().map(|p| p.0);
+                diagram.activate(&participant_name, start, colour);
+                call_stack.push(Call {
+                    callee: participant_name,
+                    caller: last_sender.clone(),
+                });
             }
             AstNode::Deactivate(participant_name) => {
                 diagram.deactivate(&participant_name)?;
+                end_call(&mut call_stack, &participant_name);
             }
-            AstNode::Note { label, direction } => match direction {
-                Direction::Left | Direction::Right => match last_message.as_ref() {
+            AstNode::Destroy(participant_name) => {
+                let row = diagram.get_timeline().len() - 1;
+                diagram.destroy(&participant_name, row)?;
+                call_stack.retain(|call| call.callee != participant_name);
+            }
+            AstNode::Note {
+                label,
+                direction,
+                shape,
+                colour,
+            } => match direction {
+                Direction::Left(None) | Direction::Right(None) => match last_message.as_ref() {
                     Some((_, msg)) => {
-                        let orientation = if let Direction::Left = direction {
-                            if msg.from < msg.to {
-                                NoteOrientation::LeftOf(msg.from.clone())
-                            } else {
-                                NoteOrientation::LeftOf(msg.to.clone())
-                            }
-                        } else if msg.from < msg.to {
-                            NoteOrientation::RightOf(msg.to.clone())
+                        let (left, right) = msg.participant_bounds();
+                        let orientation = if let Direction::Left(_) = direction {
+                            NoteOrientation::LeftOf(left)
                         } else {
-                            NoteOrientation::RightOf(msg.from.clone())
+                            NoteOrientation::RightOf(right)
                         };
-                        diagram.add_note(label, orientation, false);
+                        diagram.add_note(label, orientation, shape, colour, false);
                     }
                     None => {
                         return Err(Error::new(
@@ -154,30 +361,184 @@ pub fn create_diagram(source: &str, config: Config) -> Result<SequenceDiagram, E
                         ))
                     }
                 },
+                Direction::Left(Some(name)) => {
+                    let orientation =
+                        NoteOrientation::LeftOf(find_note_participant(&diagram, &name)?);
+                    diagram.add_note(label, orientation, shape, colour, true);
+                }
+                Direction::Right(Some(name)) => {
+                    let orientation =
+                        NoteOrientation::RightOf(find_note_participant(&diagram, &name)?);
+                    diagram.add_note(label, orientation, shape, colour, true);
+                }
                 Direction::Over(participant_names) => {
-                    let to_participant = |name: String| {
-                        diagram
-                            .find_participant_by_name(&name)
-                            .ok_or_else(|| Error::new(format!("No participant {} for note", name)))
-                    };
                     let participants: Result<Vec<Rc<RefCell<Participant>>>, Error> =
-                        participant_names.into_iter().map(to_participant).collect();
+                        participant_names
+                            .iter()
+                            .map(|name| find_note_participant(&diagram, name))
+                            .collect();
                     let orientation = NoteOrientation::Over(participants?);
-                    diagram.add_note(label, orientation, true);
+                    diagram.add_note(label, orientation, shape, colour, true);
                 }
+                Direction::Across => {
+                    diagram.add_note(label, NoteOrientation::Across, shape, colour, true);
+                }
+                Direction::OnLink => match last_message.as_ref() {
+                    Some((_, msg)) => {
+                        // The note is placed right below the arrow, centred between its ends
+                        let (left, right) = msg.participant_bounds();
+                        diagram.add_note(
+                            label,
+                            NoteOrientation::Over(vec![left, right]),
+                            shape,
+                            colour,
+                            true,
+                        );
+                    }
+                    None => {
+                        return Err(Error::new(
+                            "Adding note on link before defining any messages".to_string(),
+                        ))
+                    }
+                },
             },
             AstNode::Separator(label) => {
                 diagram.add_separator(label);
             }
+            AstNode::Delay(label) => {
+                diagram.add_delay(label);
+            }
+            AstNode::Spacer(height) => {
+                diagram.add_spacer(height);
+            }
+            AstNode::Decoration(kind, text) => {
+                let decorations = diagram.get_decorations_mut();
+                let field = match kind {
+                    DecorationKind::Title => &mut decorations.title,
+                    DecorationKind::Header => &mut decorations.header,
+                    DecorationKind::Footer => &mut decorations.footer,
+                    DecorationKind::Caption => &mut decorations.caption,
+                };
+                *field = Some(text);
+            }
+            AstNode::Legend(alignment, text) => {
+                diagram.get_decorations_mut().legend = Some(Legend { text, alignment });
+            }
+            AstNode::BoxStart { title, colour } => {
+                if open_box.is_some() {
+                    return Err(Error::new("Boxes can't be nested".to_string()));
+                }
+                let config = diagram.get_config().participant_box_config.clone();
+                open_box = Some(ParticipantBox {
+                    title,
+                    fill: colour.unwrap_or_else(|| config.background.clone()),
+                    first: diagram.get_participants().len(),
+                    last: 0,
+                    config,
+                });
+            }
+            AstNode::BoxEnd => {
+                let mut participant_box = open_box
+                    .take()
+                    .ok_or_else(|| Error::new("Found end box without open box".to_string()))?;
+                let participant_count = diagram.get_participants().len();
+                if participant_count == participant_box.first {
+                    return Err(Error::new("Box without participants".to_string()));
+                }
+                participant_box.last = participant_count - 1;
+                diagram.add_box(participant_box);
+            }
+            AstNode::Footbox(show) => diagram.set_footbox(show),
+            AstNode::Unlinked(show) => show_unlinked = show,
+            AstNode::Autoactivate(on) => autoactivate = on,
+            // The settings have been applied to the config before building the diagram
+            AstNode::Skinparam(_) => {}
+            AstNode::Autonumber(command) => match command {
+                AutonumberCommand::Start {
+                    start,
+                    step,
+                    format,
+                } => autonumber.start(start, step, format),
+                AutonumberCommand::Stop => autonumber.stop(),
+                AutonumberCommand::Resume { step, format } => autonumber.resume(step, format),
+                AutonumberCommand::Increment(level) => autonumber.increment_level(level)?,
+            },
         }
     }
 
+    if open_box.is_some() {
+        return Err(Error::new("Box with no closing end box".to_string()));
+    }
+
+    diagram.sort_participants();
+    if !show_unlinked {
+        diagram.remove_unlinked_participants();
+    }
+
+    if let Some(participant) = diagram.get_pending_creations().first() {
+        return Err(Error::new(format!(
+            "Created participant never takes part in a message: {}",
+            participant.borrow().name
+        )));
+    }
+
     match active_groups.pop_back() {
         None => Ok(diagram),
         Some(_) => Err(Error::new("Group with no closing end keyword".to_string())),
     }
 }
 
+/// Applies the settings of all the skinparam statements to the config and returns the
+/// warnings about the settings that were ignored.
+fn apply_skinparams(config: &mut Config, ast: &[AstNode]) -> Vec<String> {
+    let settings = ast.iter().flat_map(|node| match node {
+        AstNode::Skinparam(settings) => settings.as_slice(),
+        _ => &[],
+    });
+    // The default font goes first so that it doesn't override the specific ones
+    let (defaults, specifics): (Vec<_>, Vec<_>) = settings.partition(|(key, _)| {
+        key.eq_ignore_ascii_case("defaultfontsize") || key.eq_ignore_ascii_case("defaultfontname")
+    });
+    defaults
+        .into_iter()
+        .chain(specifics)
+        .filter_map(|(key, value)| apply_skinparam(config, key, value).err())
+        .collect()
+}
+
+fn add_message(
+    diagram: &mut SequenceDiagram,
+    from: &MessageParty,
+    to: &MessageParty,
+    label: String,
+    number: Option<String>,
+    arrow: Arrow,
+    new_row: bool,
+) -> Result<Message, Error> {
+    if let (MessageParty::Border(_), MessageParty::Border(_)) = (from, to) {
+        return Err(Error::new(
+            "Message between two diagram borders".to_string(),
+        ));
+    }
+    let mut message_end = |party: &MessageParty| match party {
+        MessageParty::Participant(name) => {
+            MessageEnd::Participant(diagram.get_or_create_participant(name))
+        }
+        MessageParty::Border(border) => MessageEnd::Border(*border),
+    };
+    let (from, to) = (message_end(from), message_end(to));
+    Ok(diagram.add_message(from, to, label, number, arrow, new_row))
+}
+
+fn find_note_participant(
+    diagram: &SequenceDiagram,
+    name: &str,
+) -> Result<Rc<RefCell<Participant>>, Error> {
+    diagram
+        .find_participant_by_name(name)
+        .ok_or_else(|| Error::new(format!("No participant {} for note", name)))
+}
+
 fn parse(source: &str) -> Result<Vec<AstNode>, Error> {
     let mut ast = vec![];
 
@@ -194,24 +555,42 @@ fn parse(source: &str) -> Result<Vec<AstNode>, Error> {
 
 fn build_ast_from_stmt(pair: Pair<Rule>) -> Result<AstNode, Error> {
     Ok(match pair.as_rule() {
-        Rule::participant => parse_participant(pair),
+        Rule::participant => parse_participant(pair, false)?,
+        Rule::create => parse_create(pair)?,
+        Rule::destroy => parse_destroy(pair),
+        Rule::return_message => parse_return(pair),
         Rule::message => parse_message(pair)?,
         Rule::group_start => parse_group_start(pair),
         Rule::group_end => AstNode::GroupEnd,
         Rule::alt_else => parse_alt_else(pair),
         Rule::activate => parse_activate(pair),
         Rule::deactivate => parse_deactivate(pair),
+        Rule::autoactivate => parse_autoactivate(pair),
         Rule::message_note => parse_message_note(pair)?,
         Rule::separator => parse_separator(pair),
+        Rule::autonumber => parse_autonumber(pair)?,
+        Rule::delay => parse_delay(pair),
+        Rule::spacer => parse_spacer(pair)?,
+        Rule::decoration => parse_decoration(pair)?,
+        Rule::legend => parse_legend(pair)?,
+        Rule::participant_box => parse_box(pair),
+        Rule::box_end => AstNode::BoxEnd,
+        Rule::skinparam => parse_skinparam(pair),
+        Rule::display_switch => parse_display_switch(pair),
         unknown_expr => panic!("Unexpected expression: {:?}", unknown_expr),
     })
 }
 
+/// Turns the `\n` escapes and the Creole `%newline()` in a label into line breaks.
+fn parse_line_breaks(label: &str) -> String {
+    label.replace("\\n", "\n").replace("%newline()", "\n")
+}
+
 fn parse_group_start(pair: Pair<Rule>) -> AstNode {
     let mut pair = pair.into_inner();
     let group_type = pair.next().unwrap().as_str().to_string();
     let header = match pair.next() {
-        Some(h) => h.as_str().to_string(),
+        Some(h) => parse_line_breaks(h.as_str()),
         None => "".to_string(),
     };
     AstNode::GroupStart(group_type, header)
@@ -219,88 +598,172 @@ fn parse_group_start(pair: Pair<Rule>) -> AstNode {
 
 fn parse_alt_else(pair: Pair<Rule>) -> AstNode {
     let mut pair = pair.into_inner();
-    let label = pair.next().unwrap().as_str().to_string();
+    let label = parse_line_breaks(pair.next().unwrap().as_str());
     AstNode::AltElse(label)
 }
 
 fn parse_activate(pair: Pair<Rule>) -> AstNode {
     let mut pair = pair.into_inner();
-    let label = pair.next().unwrap().as_str().to_string();
-    AstNode::Activate(label)
+    let name = parse_participant_name(&pair.next().unwrap());
+    let colour = pair.next().map(|colour| parse_colour(colour.as_str()));
+    AstNode::Activate(name, colour)
 }
 
 fn parse_deactivate(pair: Pair<Rule>) -> AstNode {
     let mut pair = pair.into_inner();
-    let label = pair.next().unwrap().as_str().to_string();
-    AstNode::Deactivate(label)
+    let name = parse_participant_name(&pair.next().unwrap());
+    AstNode::Deactivate(name)
+}
+
+fn parse_autoactivate(pair: Pair<Rule>) -> AstNode {
+    let mode = pair.into_inner().next().unwrap();
+    AstNode::Autoactivate(mode.as_str() == "on")
+}
+
+fn parse_destroy(pair: Pair<Rule>) -> AstNode {
+    let mut pair = pair.into_inner();
+    let name = parse_participant_name(&pair.next().unwrap());
+    AstNode::Destroy(name)
 }
 
-fn parse_participant(pair: Pair<Rule>) -> AstNode {
+fn parse_return(pair: Pair<Rule>) -> AstNode {
+    let mut pair = pair.into_inner().skip(1);
+    let label = parse_line_breaks(pair.next().unwrap().as_str());
+    AstNode::Return(label)
+}
+
+fn parse_create(pair: Pair<Rule>) -> Result<AstNode, Error> {
+    let inner = pair.into_inner().next().unwrap();
+    Ok(match inner.as_rule() {
+        Rule::participant => parse_participant(inner, true)?,
+        _ => ParticipantDefinition {
+            name: parse_participant_name(&inner),
+            label: parse_participant_name(&inner),
+            kind: ParticipantKind::Default,
+            created: true,
+            order: None,
+            stereotype: None,
+            colour: None,
+        },
+    })
+}
+
+fn parse_participant(pair: Pair<Rule>, created: bool) -> Result<AstNode, Error> {
     let mut pair = pair.into_inner();
     let kind = match pair.next().unwrap().as_str() {
         "participant" => ParticipantKind::Default,
         "actor" => ParticipantKind::Actor,
         "database" => ParticipantKind::Database,
+        "boundary" => ParticipantKind::Boundary,
+        "control" => ParticipantKind::Control,
+        "entity" => ParticipantKind::Entity,
+        "collections" => ParticipantKind::Collections,
+        "queue" => ParticipantKind::Queue,
         unknown => panic!("Unexpected participant type: {:?}", unknown),
     };
     let label_pair = pair.next().unwrap();
-    let label = match label_pair.as_rule() {
-        Rule::ident => label_pair.as_str(),
-        Rule::string => {
-            // Strip the leading and trailing "
-            let str = label_pair.as_str();
-            &str[1..str.len() - 1]
-        }
-        unknown_expr => panic!(
-            "Unexpected expression in participant label: {:?}",
-            unknown_expr
-        ),
-    };
+    let label = parse_participant_name(&label_pair);
 
-    let name = match pair.next() {
-        Some(inner) => inner.into_inner().next().unwrap().as_str(),
-        None => label,
-    };
+    let mut name = label.clone();
+    let mut label = label;
+    let mut order = None;
+    let mut stereotype = None;
+    let mut colour = None;
+    for inner in pair {
+        match inner.as_rule() {
+            Rule::participant_alias => {
+                let alias = inner.into_inner().next().unwrap();
+                // Either the label or the alias can come first, e.g. `"Label" as A` or
+                // `A as "Label"`, but the quoted one is always the label
+                if label_pair.as_rule() == Rule::ident && alias.as_rule() == Rule::string {
+                    label = parse_participant_name(&alias);
+                } else {
+                    name = parse_participant_name(&alias);
+                }
+            }
+            Rule::participant_order => {
+                let value = inner.into_inner().next().unwrap().as_str();
+                let parsed = value
+                    .parse::<i32>()
+                    .map_err(|_| Error::new(format!("Invalid participant order: {}", value)))?;
+                order = Some(parsed);
+            }
+            Rule::stereotype => stereotype = Some(parse_stereotype(inner)),
+            Rule::colour => colour = Some(parse_colour(inner.as_str())),
+            unknown_expr => panic!(
+                "Unexpected expression in participant definition: {:?}",
+                unknown_expr
+            ),
+        }
+    }
 
-    ParticipantDefinition {
-        name: String::from(name),
-        label: String::from(label),
+    Ok(ParticipantDefinition {
+        name,
+        label: parse_line_breaks(&label),
         kind,
+        created,
+        order,
+        stereotype,
+        colour,
+    })
+}
+
+fn parse_stereotype(pair: Pair<Rule>) -> Stereotype {
+    let mut spot = None;
+    let mut text = "";
+    for inner in pair.into_inner() {
+        match inner.as_rule() {
+            Rule::stereotype_spot => {
+                let mut spot_pairs = inner.into_inner();
+                let character = spot_pairs.next().unwrap().as_str().chars().next().unwrap();
+                let colour = parse_colour(spot_pairs.next().unwrap().as_str());
+                spot = Some(Spot { character, colour });
+            }
+            _ => text = inner.as_str(),
+        }
+    }
+    Stereotype {
+        text: text.to_string(),
+        spot,
     }
 }
 
 fn parse_message(pair: Pair<Rule>) -> Result<AstNode, Error> {
-    let mut pair = pair.into_inner();
+    let mut pair = pair.into_inner().peekable();
+    let parallel = pair.next_if(|p| p.as_rule() == Rule::parallel).is_some();
     let left_participant = pair.next().unwrap();
     let arrow = pair.next().unwrap();
     let right_participant = pair.next().unwrap();
-    let line_style = match arrow.as_str() {
-        "<-" | "->" => LineStyle::Plain,
-        "<--" | "-->" => LineStyle::Dashed,
-        _ => panic!("unexpected arrow type received"),
-    };
-    let from;
-    let to;
-    if arrow.as_str().starts_with('<') {
-        from = right_participant.as_str();
-        to = left_participant.as_str();
+    let (arrow, reversed) = parse_arrow(arrow.as_str())?;
+    let left_party = parse_message_party(&left_participant);
+    let right_party = parse_message_party(&right_participant);
+    let (from, to) = if reversed {
+        (right_party, left_party)
     } else {
-        from = left_participant.as_str();
-        to = right_participant.as_str();
+        (left_party, right_party)
     };
 
     let mut activation_modifier = None;
+    let mut lifecycle_modifier = None;
     let mut label = "";
 
     for p in pair {
         match p.as_rule() {
             Rule::activation_modifier => {
-                if p.as_str() == "++" {
-                    activation_modifier = Some(ActivationModifier::Activate);
+                if p.as_str().starts_with("++") {
+                    let colour = p.into_inner().next().map(|c| parse_colour(c.as_str()));
+                    activation_modifier = Some(ActivationModifier::Activate(colour));
                 } else {
                     activation_modifier = Some(ActivationModifier::Deactivate);
                 }
             }
+            Rule::lifecycle_modifier => {
+                if p.as_str() == "**" {
+                    lifecycle_modifier = Some(LifecycleModifier::Create);
+                } else {
+                    lifecycle_modifier = Some(LifecycleModifier::Destroy);
+                }
+            }
             Rule::message_label => {
                 label = p.into_inner().next().unwrap().as_str();
             }
@@ -313,44 +776,390 @@ fn parse_message(pair: Pair<Rule>) -> Result<AstNode, Error> {
     }
 
     Ok(AstNode::Message {
-        from: String::from(from),
-        to: String::from(to),
-        label: String::from(label),
-        style: line_style,
+        from,
+        to,
+        label: parse_line_breaks(label),
+        arrow,
         activation_modifier,
+        lifecycle_modifier,
+        parallel,
     })
 }
 
+fn parse_message_party(pair: &Pair<Rule>) -> MessageParty {
+    match (pair.as_rule(), pair.as_str()) {
+        (Rule::left_border, "[") => MessageParty::Border(Border::Left),
+        (Rule::left_border, _) => MessageParty::Border(Border::ShortLeft),
+        (Rule::right_border, "]") => MessageParty::Border(Border::Right),
+        (Rule::right_border, _) => MessageParty::Border(Border::ShortRight),
+        _ => MessageParty::Participant(parse_participant_name(pair)),
+    }
+}
+
+/// Parses the arrow into its line style and the decorations on both of its ends.
+/// The returned flag is true if the arrow points from right to left, in which case the
+/// ends are already swapped so that the start belongs to the sender.
+fn parse_arrow(arrow: &str) -> Result<(Arrow, bool), Error> {
+    // Cut the style modifiers out of the shaft, the grammar allows them after the first dash
+    let (shape, modifiers) = match (arrow.find('['), arrow.rfind(']')) {
+        (Some(open), Some(close)) => (
+            format!("{}{}", &arrow[..open], &arrow[close + 1..]),
+            Some(&arrow[open + 1..close]),
+        ),
+        _ => (arrow.to_string(), None),
+    };
+
+    // The grammar guarantees that the shaft is one or two dashes with no dashes in the heads
+    let shaft_start = shape.find('-').unwrap();
+    let shaft_end = shape.rfind('-').unwrap() + 1;
+    let style = if shaft_end - shaft_start > 1 {
+        LineStyle::Dashed
+    } else {
+        LineStyle::Plain
+    };
+
+    let left = parse_arrow_end(&shape[..shaft_start], true);
+    let right = parse_arrow_end(&shape[shaft_end..], false);
+    if left.is_plain() && right.is_plain() {
+        return Err(Error::new(format!("Arrow with no head: {}", arrow)));
+    }
+
+    let reversed = left.head != ArrowHead::None && right.head == ArrowHead::None;
+    let mut parsed = if reversed {
+        Arrow::new(style, right, left)
+    } else {
+        Arrow::new(style, left, right)
+    };
+    if let Some(modifiers) = modifiers {
+        apply_arrow_modifiers(&mut parsed, modifiers)?;
+    }
+    Ok((parsed, reversed))
+}
+
+/// Applies the comma-separated modifiers of an arrow such as `#red,bold` or `dotted`.
+fn apply_arrow_modifiers(arrow: &mut Arrow, modifiers: &str) -> Result<(), Error> {
+    for modifier in modifiers.split(',').map(str::trim) {
+        let lower = modifier.to_lowercase();
+        match lower.as_str() {
+            "" => {}
+            "plain" => arrow.style = LineStyle::Plain,
+            "dashed" => arrow.style = LineStyle::Dashed,
+            "dotted" => arrow.style = LineStyle::Dotted,
+            "hidden" => arrow.style = LineStyle::Hidden,
+            "bold" => arrow.thickness = 2,
+            _ if modifier.starts_with('#') && modifier.len() > 1 => {
+                arrow.colour = parse_colour(modifier)
+            }
+            _ => {
+                let thickness = lower
+                    .strip_prefix("thickness=")
+                    .and_then(|t| t.trim().parse().ok())
+                    .ok_or_else(|| Error::new(format!("Unknown arrow style: {}", modifier)))?;
+                arrow.thickness = thickness;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_arrow_end(end: &str, left: bool) -> ArrowEnd {
+    let (head, circle) = if left {
+        match end.strip_prefix('o') {
+            Some(head) => (head, true),
+            None => (end, false),
+        }
+    } else {
+        match end.strip_suffix('o') {
+            Some(head) => (head, true),
+            None => (end, false),
+        }
+    };
+    // Half heads are named after the side of the line they are drawn on, which depends
+    // on which way the slash leans relative to the shaft
+    let head = match head.trim_matches(|c| c == '<' || c == '>') {
+        _ if head.contains('x') => ArrowHead::Cross,
+        "" if head.len() == 2 => ArrowHead::Thin,
+        "" if head.len() == 1 => ArrowHead::Filled,
+        "\\\\" if left => ArrowHead::ThinHalfLower,
+        "\\\\" => ArrowHead::ThinHalfUpper,
+        "\\" if left => ArrowHead::HalfLower,
+        "\\" => ArrowHead::HalfUpper,
+        "//" if left => ArrowHead::ThinHalfUpper,
+        "//" => ArrowHead::ThinHalfLower,
+        "/" if left => ArrowHead::HalfUpper,
+        "/" => ArrowHead::HalfLower,
+        _ => ArrowHead::None,
+    };
+    ArrowEnd::new(head, circle)
+}
+
 fn parse_message_note(pair: Pair<Rule>) -> Result<AstNode, Error> {
     let mut pairs = pair.into_inner();
+    let shape = match pairs.next().unwrap().as_str() {
+        "hnote" => NoteShape::Hexagon,
+        "rnote" => NoteShape::Rectangle,
+        _ => NoteShape::Folded,
+    };
     let direction_pair = pairs.next().unwrap().into_inner().next().unwrap();
     let direction = match direction_pair.as_rule() {
-        Rule::note_left => Direction::Left,
-        Rule::note_right => Direction::Right,
+        Rule::note_left => Direction::Left(
+            direction_pair
+                .into_inner()
+                .next()
+                .map(|pair| parse_participant_name(&pair)),
+        ),
+        Rule::note_right => Direction::Right(
+            direction_pair
+                .into_inner()
+                .next()
+                .map(|pair| parse_participant_name(&pair)),
+        ),
+        Rule::note_across => Direction::Across,
+        Rule::note_on_link => Direction::OnLink,
         Rule::note_over => {
             let participants = direction_pair
                 .into_inner()
-                .map(|pair| pair.as_str().to_string())
+                .map(|pair| parse_participant_name(&pair))
                 .collect();
             Direction::Over(participants)
         }
         _ => return Err(Error::new("Unexpected note orientation".to_string())),
     };
 
-    let label = pairs
-        .next()
-        .unwrap()
-        .into_inner()
-        .next()
-        .unwrap()
-        .as_str()
-        .replace("\\n", "\n");
+    let mut colour = None;
+    let mut label_pair = pairs.next().unwrap();
+    if let Rule::colour = label_pair.as_rule() {
+        colour = Some(parse_colour(label_pair.as_str()));
+        label_pair = pairs.next().unwrap();
+    }
+    let is_multiline = label_pair.as_rule() == Rule::note_multiline_label;
+    let label = label_pair.into_inner().next().unwrap().as_str();
+    let label = if is_multiline {
+        strip_comments(label)
+    } else {
+        label.to_string()
+    };
+    let label = parse_line_breaks(&label);
 
-    Ok(AstNode::Note { label, direction })
+    Ok(AstNode::Note {
+        label,
+        direction,
+        shape,
+        colour,
+    })
 }
 
 fn parse_separator(pair: Pair<Rule>) -> AstNode {
     let mut pair = pair.into_inner();
-    let label = pair.next().unwrap().as_str().to_string();
+    let label = parse_line_breaks(pair.next().unwrap().as_str());
     AstNode::Separator(label)
 }
+
+fn parse_delay(pair: Pair<Rule>) -> AstNode {
+    let label = pair
+        .into_inner()
+        .next()
+        .map(|label| label.as_str().trim().to_string());
+    AstNode::Delay(label)
+}
+
+fn parse_spacer(pair: Pair<Rule>) -> Result<AstNode, Error> {
+    let height = match pair.into_inner().next() {
+        Some(height) => Some(
+            height
+                .as_str()
+                .parse::<u32>()
+                .map_err(|_| Error::new(format!("Invalid spacer height: {}", height.as_str())))?,
+        ),
+        None => None,
+    };
+    Ok(AstNode::Spacer(height))
+}
+
+fn parse_autonumber(pair: Pair<Rule>) -> Result<AstNode, Error> {
+    let command = match pair.into_inner().next() {
+        None => AutonumberCommand::Start {
+            start: None,
+            step: None,
+            format: None,
+        },
+        Some(command) => match command.as_rule() {
+            Rule::autonumber_stop => AutonumberCommand::Stop,
+            Rule::autonumber_inc => {
+                let level = command.into_inner().next().unwrap().as_str();
+                AutonumberCommand::Increment((level.as_bytes()[0] - b'A') as usize)
+            }
+            Rule::autonumber_resume => {
+                let (_, step, format) = parse_autonumber_args(command)?;
+                AutonumberCommand::Resume { step, format }
+            }
+            Rule::autonumber_start => {
+                let (start, step, format) = parse_autonumber_args(command)?;
+                AutonumberCommand::Start {
+                    start,
+                    step,
+                    format,
+                }
+            }
+            _ => return Err(Error::new("Unexpected autonumber command".to_string())),
+        },
+    };
+    Ok(AstNode::Autonumber(command))
+}
+
+type AutonumberArgs = (Option<Vec<u32>>, Option<u32>, Option<String>);
+
+fn parse_autonumber_args(pair: Pair<Rule>) -> Result<AutonumberArgs, Error> {
+    let parse_number = |s: &str| {
+        s.parse::<u32>()
+            .map_err(|_| Error::new(format!("Invalid autonumber value: {}", s)))
+    };
+
+    let mut start = None;
+    let mut step = None;
+    let mut format = None;
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::autonumber_value => {
+                let levels: Result<Vec<u32>, Error> =
+                    p.as_str().split('.').map(parse_number).collect();
+                start = Some(levels?);
+            }
+            Rule::autonumber_step => step = Some(parse_number(p.as_str())?),
+            Rule::string => format = Some(unquote(p.as_str()).to_string()),
+            _ => return Err(Error::new("Unexpected autonumber argument".to_string())),
+        }
+    }
+    Ok((start, step, format))
+}
+
+fn parse_decoration(pair: Pair<Rule>) -> Result<AstNode, Error> {
+    let mut pairs = pair.into_inner();
+    let kind = match pairs.next().unwrap().as_str() {
+        "title" => DecorationKind::Title,
+        "header" => DecorationKind::Header,
+        "footer" => DecorationKind::Footer,
+        "caption" => DecorationKind::Caption,
+        unknown => return Err(Error::new(format!("Unexpected decoration: {}", unknown))),
+    };
+    let text = pairs.next().unwrap();
+    let text = match text.as_rule() {
+        Rule::decoration_block => parse_block_text(text),
+        _ => parse_line_breaks(text.as_str()),
+    };
+    Ok(AstNode::Decoration(kind, text))
+}
+
+fn parse_display_switch(pair: Pair<Rule>) -> AstNode {
+    let mut pairs = pair.into_inner();
+    let show = pairs.next().unwrap().as_str() == "show";
+    match pairs.next().unwrap().as_str() {
+        "footbox" => AstNode::Footbox(show),
+        _ => AstNode::Unlinked(show),
+    }
+}
+
+fn parse_skinparam(pair: Pair<Rule>) -> AstNode {
+    let parse_entry = |entry: Pair<Rule>, prefix: &str| {
+        let mut pairs = entry.into_inner();
+        let key = format!("{}{}", prefix, pairs.next().unwrap().as_str());
+        (key, pairs.next().unwrap().as_str().to_string())
+    };
+
+    let pair = pair.into_inner().next().unwrap();
+    let settings = match pair.as_rule() {
+        Rule::skinparam_block => {
+            // The keys in a block are prefixed with the block's name
+            let mut pairs = pair.into_inner();
+            let prefix = pairs.next().unwrap().as_str().to_string();
+            pairs.map(|entry| parse_entry(entry, &prefix)).collect()
+        }
+        _ => vec![parse_entry(pair, "")],
+    };
+    AstNode::Skinparam(settings)
+}
+
+fn parse_box(pair: Pair<Rule>) -> AstNode {
+    let mut title = None;
+    let mut colour = None;
+    for pair in pair.into_inner() {
+        match pair.as_rule() {
+            Rule::string => title = Some(unquote(pair.as_str()).to_string()),
+            Rule::colour => colour = Some(parse_colour(pair.as_str())),
+            _ => {}
+        }
+    }
+    AstNode::BoxStart { title, colour }
+}
+
+fn parse_legend(pair: Pair<Rule>) -> Result<AstNode, Error> {
+    let mut alignment = Alignment::Center;
+    let mut text = String::new();
+    for pair in pair.into_inner() {
+        match pair.as_rule() {
+            Rule::legend_alignment => {
+                alignment = match pair.as_str() {
+                    "left" => Alignment::Left,
+                    "right" => Alignment::Right,
+                    "center" => Alignment::Center,
+                    unknown => {
+                        return Err(Error::new(format!(
+                            "Unexpected legend alignment: {}",
+                            unknown
+                        )))
+                    }
+                }
+            }
+            Rule::decoration_block => text = parse_block_text(pair),
+            _ => {}
+        }
+    }
+    Ok(AstNode::Legend(alignment, text))
+}
+
+/// Returns the text of a `... end <keyword>` block with the lines trimmed.
+fn parse_block_text(pair: Pair<Rule>) -> String {
+    let text = pair.into_inner().next().unwrap().as_str();
+    strip_comments(text)
+        .lines()
+        .map(str::trim)
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+/// Removes the block comments and the comment lines from a multiline label, which the
+/// grammar captures verbatim.
+fn strip_comments(label: &str) -> String {
+    let mut without_blocks = String::new();
+    let mut rest = label;
+    while let Some(start) = rest.find("/'") {
+        without_blocks.push_str(&rest[..start]);
+        rest = match rest[start..].find("'/") {
+            Some(end) => &rest[start + end + 2..],
+            None => "",
+        };
+    }
+    without_blocks.push_str(rest);
+
+    without_blocks
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('\''))
+        .collect::<Vec<&str>>()
+        .join("\n")
+        // The grammar skips comments before the first line, leaving the line break behind
+        .trim_start()
+        .to_string()
+}
+
+/// Returns the name of a participant referenced by an identifier or a quoted string.
+fn parse_participant_name(pair: &Pair<Rule>) -> String {
+    match pair.as_rule() {
+        Rule::string => unquote(pair.as_str()).to_string(),
+        _ => pair.as_str().to_string(),
+    }
+}
+
+/// Strips the leading and trailing " from a string literal.
+fn unquote(s: &str) -> &str {
+    &s[1..s.len() - 1]
+}
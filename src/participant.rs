@@ -1,15 +1,32 @@
 use crate::diagram::{SequenceDiagram, TimelineEvent};
 use crate::message::ARROW_DISTANCE_FROM_BOTTOM;
-use crate::rendering::layout::{string_width, GridSize};
-use crate::rendering::renderer::{RectParams, Renderer, MEDIUM_BLUE};
+use crate::rendering::font::Font;
+use crate::rendering::layout::{string_width, text_height, text_width, GridSize};
+use crate::rendering::renderer::{RectParams, Renderer, DARK_RED};
 use nalgebra::Point2;
 use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::rc::Rc;
 
-pub const PARTICIPANT_SPACE: u32 = 20;
 pub const ACTIVATION_WIDTH: u32 = 10;
 pub const ACTIVATION_NESTING_OFFSET: u32 = 3;
+pub const COLLECTIONS_OFFSET: u32 = 4;
+pub const DESTRUCTION_SIZE: u32 = 16;
+pub const SPOT_MARGIN: u32 = 4;
+
+/// A stereotype shown in guillemets above the label of a participant, e.g. `<<REST>>`.
+#[derive(Debug, Clone)]
+pub struct Stereotype {
+    pub text: String,
+    pub spot: Option<Spot>,
+}
+
+/// A character in a coloured circle shown to the left of the label, e.g. `(C,#ADD1B2)`.
+#[derive(Debug, Clone)]
+pub struct Spot {
+    pub character: char,
+    pub colour: String,
+}
 
 #[derive(Debug, Clone)]
 pub struct Participant {
@@ -17,7 +34,16 @@ pub struct Participant {
     label: String,
     kind: ParticipantKind,
     pub idx: usize,
+    /// The participants are sorted by this, keeping the order they are declared in otherwise
+    order: i32,
     activations: Vec<Activation>,
+    created: Option<usize>,
+    destroyed: Option<usize>,
+    /// Whether the participant takes part in a message or has a note attached
+    linked: bool,
+    stereotype: Option<Stereotype>,
+    /// The fill colour if it's different from the default one in the config
+    fill: Option<String>,
     config: ParticipantConfig,
 }
 
@@ -38,15 +64,78 @@ impl Participant {
             label,
             kind,
             idx: 0,
+            order: 0,
             activations: vec![],
+            created: None,
+            destroyed: None,
+            linked: false,
+            stereotype: None,
+            fill: None,
             config,
         }
     }
 
+    pub fn get_order(&self) -> i32 {
+        self.order
+    }
+
+    pub fn set_order(&mut self, order: i32) {
+        self.order = order;
+    }
+
     pub fn get_label(&self) -> &String {
         &self.label
     }
 
+    pub fn set_stereotype(&mut self, stereotype: Stereotype) {
+        self.stereotype = Some(stereotype);
+    }
+
+    /// Sets the fill colour, overriding the background colour of the config.
+    pub fn set_fill(&mut self, fill: String) {
+        self.fill = Some(fill);
+    }
+
+    /// Returns the label with the stereotype, if it has one, on the line above it.
+    pub fn display_label(&self) -> String {
+        match &self.stereotype {
+            Some(stereotype) if !stereotype.text.is_empty() => {
+                format!("<i>«{}»</i>\n{}", stereotype.text, self.label)
+            }
+            _ => self.label.clone(),
+        }
+    }
+
+    fn spot(&self) -> Option<&Spot> {
+        self.stereotype.as_ref()?.spot.as_ref()
+    }
+
+    /// The width of the spot drawn to the left of the label, including its margin.
+    fn spot_width(&self) -> u32 {
+        match self.spot() {
+            Some(_) => self.config.font_size + SPOT_MARGIN,
+            None => 0,
+        }
+    }
+
+    /// The height the label takes up in addition to a single line of text.
+    fn extra_label_height(&self) -> u32 {
+        text_height(&self.display_label(), self.config.font_size) - self.config.font_size
+    }
+
+    fn fill(&self) -> &str {
+        self.fill.as_ref().unwrap_or(&self.config.background)
+    }
+
+    /// Returns the rectangle parameters using the participant's colours.
+    fn rect_params(&self) -> RectParams<'_> {
+        RectParams {
+            fill: self.fill(),
+            stroke: &self.config.border,
+            ..Default::default()
+        }
+    }
+
     pub fn get_kind(&self) -> &ParticipantKind {
         &self.kind
     }
@@ -55,10 +144,11 @@ impl Participant {
         self.idx
     }
 
-    pub fn activate(&mut self, start: Option<usize>) {
+    /// Starts an activation at the supplied row, filled with the colour if one is given.
+    pub fn activate(&mut self, start: Option<usize>, colour: Option<String>) {
         let nesting = self.activations.iter().filter(|&a| a.end.is_none()).count();
         self.activations
-            .push(Activation::new(start, nesting as u32));
+            .push(Activation::new(start, nesting as u32, colour));
     }
 
     pub fn deactivate(&mut self, end: usize) -> bool {
@@ -72,11 +162,44 @@ impl Participant {
         }
     }
 
+    /// Marks the participant as created at the supplied row, rather than being present
+    /// from the start of the diagram.
+    pub fn create(&mut self, row: usize) {
+        self.created = Some(row);
+    }
+
+    /// Ends the participant's lifeline at the supplied row, closing any of its activations
+    /// that are still open.
+    pub fn destroy(&mut self, row: usize) {
+        for activation in self.activations.iter_mut().filter(|a| a.end.is_none()) {
+            activation.end(row);
+        }
+        self.destroyed = Some(row);
+    }
+
+    pub fn is_destroyed(&self) -> bool {
+        self.destroyed.is_some()
+    }
+
+    pub fn link(&mut self) {
+        self.linked = true;
+    }
+
+    pub fn is_linked(&self) -> bool {
+        self.linked
+    }
+
     fn count_activations_at(&self, row: usize) -> usize {
         self.activations.iter().filter(|&a| a.contains(row)).count()
     }
 
     pub fn lifeline_offset(&self, row: usize) -> (i32, i32) {
+        // Messages creating the participant point at its box rather than the lifeline
+        if self.created == Some(row) {
+            let half_width = (get_rendered_width(self) / 2) as i32;
+            return (-half_width, half_width);
+        }
+
         let count = self.count_activations_at(row);
         if count > 0 {
             (
@@ -115,14 +238,16 @@ pub struct Activation {
     start: Option<usize>,
     end: Option<usize>,
     nesting: u32,
+    colour: Option<String>,
 }
 
 impl Activation {
-    fn new(start: Option<usize>, nesting: u32) -> Activation {
+    fn new(start: Option<usize>, nesting: u32, colour: Option<String>) -> Activation {
         Activation {
             start,
             end: None,
             nesting,
+            colour,
         }
     }
 
@@ -148,6 +273,11 @@ pub enum ParticipantKind {
     Default,
     Actor,
     Database,
+    Boundary,
+    Control,
+    Entity,
+    Collections,
+    Queue,
 }
 
 pub struct ParticipantCreated {
@@ -157,7 +287,7 @@ pub struct ParticipantCreated {
 impl TimelineEvent for ParticipantCreated {
     fn draw(
         &self,
-        _diagram: &SequenceDiagram,
+        diagram: &SequenceDiagram,
         renderer: &mut dyn Renderer,
         grid: &GridSize,
         row: usize,
@@ -165,14 +295,34 @@ impl TimelineEvent for ParticipantCreated {
         let participant = self.participant.borrow();
         let center_x = grid.get_col_center(participant.get_idx());
 
+        // Participants created later in the diagram are centred on the creating message
+        let top_y = match participant.created {
+            Some(row) => grid.get_row_bottom(row) - ARROW_DISTANCE_FROM_BOTTOM - self.height() / 2,
+            None => grid.get_row_bottom(row) - self.height(),
+        };
+        let last_row = grid.num_rows() - 1;
+        let (timeline_end_y, footbox_y) = if diagram.shows_footbox() {
+            (
+                grid.get_row_bottom(last_row - 1),
+                Some(grid.get_row_top(last_row)),
+            )
+        } else {
+            (grid.get_row_bottom(last_row), None)
+        };
+        let lifeline_end_y = match participant.destroyed {
+            Some(row) => grid.get_row_bottom(row) - ARROW_DISTANCE_FROM_BOTTOM,
+            None => footbox_y.unwrap_or(timeline_end_y),
+        };
+
         // render lifeline
-        renderer.render_line(
-            Point2::new(center_x, grid.get_row_bottom(row)),
-            Point2::new(center_x, grid.get_row_top(grid.num_rows() - 1)),
-            3,
-            0,
-            MEDIUM_BLUE,
-            None,
+        draw_lifeline(
+            renderer,
+            diagram,
+            grid,
+            &participant.config,
+            center_x,
+            top_y + self.height(),
+            lifeline_end_y,
         );
 
         // render activation boxes
@@ -181,58 +331,149 @@ impl TimelineEvent for ParticipantCreated {
                 center_x - ACTIVATION_WIDTH / 2 + activation.nesting * ACTIVATION_NESTING_OFFSET;
             let start_y = match activation.start {
                 Some(row) => grid.get_row_bottom(row) - ARROW_DISTANCE_FROM_BOTTOM,
-                None => grid.get_row_top(1),
+                None => top_y + self.height(),
             };
             let end_y = match activation.end {
                 Some(row) => grid.get_row_bottom(row) - ARROW_DISTANCE_FROM_BOTTOM,
-                None => grid.get_row_bottom(grid.num_rows() - 2),
-            };
-            let params = RectParams {
-                ..Default::default()
+                None => timeline_end_y,
             };
+            let mut params = RectParams::default();
+            if let Some(colour) = &activation.colour {
+                params.fill = colour;
+            }
             renderer.render_rect(x, start_y, ACTIVATION_WIDTH, end_y - start_y, params);
         }
 
         // render participant at the top
-        draw_participant(
-            &participant,
-            renderer,
-            center_x,
-            grid.get_row_bottom(row) - self.height(),
-            self.height(),
-        );
+        draw_participant(&participant, renderer, center_x, top_y, self.height());
 
-        // render participant at the bottom
-        draw_participant(
-            &participant,
-            renderer,
-            center_x,
-            grid.get_row_top(grid.num_rows() - 1),
-            self.height(),
-        );
+        // render participant at the bottom, or the cross marking the end of its lifeline
+        if participant.is_destroyed() {
+            draw_destruction(renderer, center_x, lifeline_end_y);
+        } else if let Some(y) = footbox_y {
+            draw_participant(&participant, renderer, center_x, y, self.height());
+        }
     }
 
     fn height(&self) -> u32 {
-        let font_size = self.participant.borrow().config.font_size;
-        match self.participant.borrow().get_kind() {
+        let participant = self.participant.borrow();
+        let font_size = participant.config.font_size;
+        let icon_height = match participant.get_kind() {
             ParticipantKind::Default => font_size * 2,
             ParticipantKind::Actor => font_size * 4,
             ParticipantKind::Database => font_size * 3,
-        }
+            ParticipantKind::Boundary | ParticipantKind::Control | ParticipantKind::Entity => {
+                font_size * 3
+            }
+            ParticipantKind::Collections => font_size * 2 + COLLECTIONS_OFFSET,
+            ParticipantKind::Queue => font_size * 2,
+        };
+        icon_height + participant.extra_label_height()
     }
 
     fn col_range(&self) -> Option<(usize, usize)> {
         let col = self.participant.borrow().get_idx();
         Some((col, col))
     }
+
+    fn drawn_participant(&self) -> Option<&Rc<RefCell<Participant>>> {
+        Some(&self.participant)
+    }
+}
+
+/// Draws the lifeline between the two y coordinates. The lifeline is dotted in the rows
+/// of delays and solid (or dashed, depending on the config) everywhere else.
+fn draw_lifeline(
+    renderer: &mut dyn Renderer,
+    diagram: &SequenceDiagram,
+    grid: &GridSize,
+    config: &ParticipantConfig,
+    x: u32,
+    start_y: u32,
+    end_y: u32,
+) {
+    let mut render_segment = |y1: u32, y2: u32, dash: u8| {
+        renderer.render_line(
+            Point2::new(x, y1),
+            Point2::new(x, y2),
+            3,
+            dash,
+            &config.lifeline_colour,
+            None,
+        );
+    };
+    let line_dash = if config.lifeline_dashed { 8 } else { 0 };
+
+    let mut y = start_y;
+    for (row, events) in diagram.get_timeline().iter().enumerate() {
+        let top = grid.get_row_top(row);
+        let bottom = grid.get_row_bottom(row);
+        if top < y || bottom > end_y || !events.iter().any(|ev| ev.is_delay()) {
+            continue;
+        }
+        render_segment(y, top, line_dash);
+        render_segment(top, bottom, 4);
+        y = bottom;
+    }
+    render_segment(y, end_y, line_dash);
+}
+
+fn draw_destruction(renderer: &mut dyn Renderer, x: u32, y: u32) {
+    let size = DESTRUCTION_SIZE / 2;
+    renderer.render_line(
+        Point2::new(x - size, y - size),
+        Point2::new(x + size, y + size),
+        2,
+        0,
+        DARK_RED,
+        None,
+    );
+    renderer.render_line(
+        Point2::new(x - size, y + size),
+        Point2::new(x + size, y - size),
+        2,
+        0,
+        DARK_RED,
+        None,
+    );
 }
 
 pub fn get_participant_width(participant: &Participant) -> u32 {
-    get_rendered_width(participant) + PARTICIPANT_SPACE
+    get_rendered_width(participant) + participant.config.padding
+}
+
+pub fn get_rendered_width(participant: &Participant) -> u32 {
+    text_width(
+        &participant.display_label(),
+        &participant.config.font,
+        participant.config.font_size,
+    ) + participant.spot_width()
+        + 50
 }
 
-fn get_rendered_width(participant: &Participant) -> u32 {
-    string_width(participant.get_label(), participant.config.font_size) + 50
+/// Draws the label with its top at y and its first line centred at x, or right of x if
+/// there is a spot to its left.
+fn draw_label(renderer: &mut dyn Renderer, participant: &Participant, x: u32, y: u32) {
+    let config = &participant.config;
+    let label = participant.display_label();
+    let text_x = x + participant.spot_width() / 2;
+    if let Some(spot) = participant.spot() {
+        let first_line = label.split('\n').next().unwrap();
+        let first_line_width = string_width(first_line, &config.font, config.font_size);
+        let r = config.font_size / 2;
+        let center = Point2::new(text_x - first_line_width / 2 - SPOT_MARGIN - r, y + r);
+        renderer.render_outlined_circle(center, r, &spot.colour, &config.border);
+        let spot_font_size = config.font_size * 3 / 4;
+        renderer.render_text(
+            &spot.character.to_string(),
+            center.x,
+            center.y - spot_font_size / 2,
+            &config.font,
+            spot_font_size,
+            "middle",
+        );
+    }
+    renderer.render_text(&label, text_x, y, &config.font, config.font_size, "middle");
 }
 
 pub fn draw_participant(
@@ -246,6 +487,11 @@ pub fn draw_participant(
         ParticipantKind::Default => draw_default_participant(renderer, participant, x, y, height),
         ParticipantKind::Actor => draw_actor(renderer, participant, x, y, height),
         ParticipantKind::Database => draw_database(renderer, participant, x, y, height),
+        ParticipantKind::Boundary => draw_boundary(renderer, participant, x, y, height),
+        ParticipantKind::Control => draw_control(renderer, participant, x, y, height),
+        ParticipantKind::Entity => draw_entity(renderer, participant, x, y, height),
+        ParticipantKind::Collections => draw_collections(renderer, participant, x, y, height),
+        ParticipantKind::Queue => draw_queue(renderer, participant, x, y, height),
     }
 }
 
@@ -260,15 +506,14 @@ fn draw_default_participant(
     let font_size = participant.config.font_size;
     let rect_params = RectParams {
         r: font_size / 4,
-        ..Default::default()
+        ..participant.rect_params()
     };
     renderer.render_rect(x - width / 2, y, width, height, rect_params);
-    renderer.render_text(
-        &participant.get_label(),
+    draw_label(
+        renderer,
+        participant,
         x,
-        y + (height - font_size) / 2,
-        font_size,
-        "middle",
+        y + (height - text_height(&participant.display_label(), font_size)) / 2,
     );
 }
 
@@ -279,16 +524,16 @@ pub fn draw_actor(
     y: u32,
     height: u32,
 ) {
-    let stickman_height = height * 2 / 3;
+    let stickman_height = (height - participant.extra_label_height()) * 2 / 3;
     let stickman_width = stickman_height * 2 / 3;
-    renderer.render_stickman(x, y + stickman_height, stickman_width, stickman_height);
-    renderer.render_text(
-        &participant.get_label(),
+    renderer.render_stickman(
         x,
         y + stickman_height,
-        participant.config.font_size,
-        "middle",
+        stickman_width,
+        stickman_height,
+        participant.rect_params(),
     );
+    draw_label(renderer, participant, x, y + stickman_height);
 }
 
 fn draw_database(
@@ -299,19 +544,173 @@ fn draw_database(
     height: u32,
 ) {
     let font_size = participant.config.font_size;
-    let width = string_width(&participant.get_label(), font_size);
+    let width = text_width(
+        &participant.display_label(),
+        &participant.config.font,
+        font_size,
+    ) + participant.spot_width();
 
-    renderer.render_db_icon(x, y + height, width * 3 / 2, height);
-    renderer.render_text(
-        &participant.get_label(),
+    renderer.render_db_icon(
         x,
-        y + height - font_size * 11 / 6,
-        font_size,
-        "middle",
+        y + height,
+        width * 3 / 2,
+        height,
+        participant.rect_params(),
+    );
+    draw_label(
+        renderer,
+        participant,
+        x,
+        y + height - participant.extra_label_height() - font_size * 11 / 6,
+    );
+}
+
+/// Draws the circle shared by the boundary, control and entity icons with the label below
+/// it and returns the centre and the radius of the circle.
+fn draw_circle_icon(
+    renderer: &mut dyn Renderer,
+    participant: &Participant,
+    x: u32,
+    y: u32,
+    height: u32,
+) -> (Point2<u32>, u32) {
+    let icon_height = (height - participant.extra_label_height()) * 2 / 3;
+    let r = (icon_height / 2).saturating_sub(2).max(1);
+    let center = Point2::new(x, y + icon_height / 2);
+    renderer.render_outlined_circle(center, r, participant.fill(), &participant.config.border);
+    draw_label(renderer, participant, x, y + icon_height);
+    (center, r)
+}
+
+fn draw_boundary(
+    renderer: &mut dyn Renderer,
+    participant: &Participant,
+    x: u32,
+    y: u32,
+    height: u32,
+) {
+    let (center, r) = draw_circle_icon(renderer, participant, x, y, height);
+
+    // The boundary is a vertical bar to the left of the circle connected to it with a line
+    let bar_x = center.x - r * 2;
+    renderer.render_line(
+        Point2::new(bar_x, center.y),
+        Point2::new(center.x - r, center.y),
+        2,
+        0,
+        &participant.config.border,
+        None,
+    );
+    renderer.render_line(
+        Point2::new(bar_x, center.y - r),
+        Point2::new(bar_x, center.y + r),
+        2,
+        0,
+        &participant.config.border,
+        None,
+    );
+}
+
+fn draw_control(
+    renderer: &mut dyn Renderer,
+    participant: &Participant,
+    x: u32,
+    y: u32,
+    height: u32,
+) {
+    let (center, r) = draw_circle_icon(renderer, participant, x, y, height);
+
+    // The control has an arrow head on top of the circle pointing left
+    let head_size = r / 3;
+    let tip = Point2::new(center.x - head_size / 2, center.y - r);
+    for head_y in &[tip.y - head_size, tip.y + head_size] {
+        renderer.render_line(
+            tip,
+            Point2::new(tip.x + head_size, *head_y),
+            2,
+            0,
+            &participant.config.border,
+            None,
+        );
+    }
+}
+
+fn draw_entity(
+    renderer: &mut dyn Renderer,
+    participant: &Participant,
+    x: u32,
+    y: u32,
+    height: u32,
+) {
+    let (center, r) = draw_circle_icon(renderer, participant, x, y, height);
+
+    // The entity is underlined by a horizontal line touching the circle
+    renderer.render_line(
+        Point2::new(center.x - r, center.y + r),
+        Point2::new(center.x + r, center.y + r),
+        2,
+        0,
+        &participant.config.border,
+        None,
+    );
+}
+
+fn draw_collections(
+    renderer: &mut dyn Renderer,
+    participant: &Participant,
+    x: u32,
+    y: u32,
+    height: u32,
+) {
+    let width = get_rendered_width(participant);
+    let box_height = height - COLLECTIONS_OFFSET;
+    let font_size = participant.config.font_size;
+
+    // The box at the back is offset to the top right of the front one
+    renderer.render_rect(
+        x - width / 2 + COLLECTIONS_OFFSET,
+        y,
+        width,
+        box_height,
+        participant.rect_params(),
+    );
+    renderer.render_rect(
+        x - width / 2,
+        y + COLLECTIONS_OFFSET,
+        width,
+        box_height,
+        participant.rect_params(),
+    );
+    draw_label(
+        renderer,
+        participant,
+        x,
+        y + COLLECTIONS_OFFSET
+            + (box_height - text_height(&participant.display_label(), font_size)) / 2,
+    );
+}
+
+fn draw_queue(renderer: &mut dyn Renderer, participant: &Participant, x: u32, y: u32, height: u32) {
+    let width = get_rendered_width(participant);
+    let font_size = participant.config.font_size;
+
+    renderer.render_queue_icon(x, y, width, height, participant.rect_params());
+    draw_label(
+        renderer,
+        participant,
+        x,
+        y + (height - text_height(&participant.display_label(), font_size)) / 2,
     );
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub struct ParticipantConfig {
     pub font_size: u32,
+    pub font: Font,
+    /// The horizontal space between two participants
+    pub padding: u32,
+    pub background: String,
+    pub border: String,
+    pub lifeline_colour: String,
+    pub lifeline_dashed: bool,
 }
@@ -0,0 +1,108 @@
+use crate::diagram::SequenceDiagram;
+use crate::participant::get_rendered_width;
+use crate::rendering::font::Font;
+use crate::rendering::layout::{text_width, GridSize};
+use crate::rendering::renderer::{RectParams, Renderer};
+
+/// A coloured box drawn behind a contiguous range of participants.
+pub struct ParticipantBox {
+    pub title: Option<String>,
+    pub fill: String,
+    /// The index of the first participant in the box.
+    pub first: usize,
+    /// The index of the last participant in the box.
+    pub last: usize,
+    pub config: ParticipantBoxConfig,
+}
+
+impl ParticipantBox {
+    fn title_width(&self) -> u32 {
+        match &self.title {
+            Some(title) => text_width(title, &self.config.font, self.config.font_size),
+            None => 0,
+        }
+    }
+
+    /// The height of the title, which is drawn above the participants.
+    pub fn title_height(&self) -> u32 {
+        match &self.title {
+            Some(title) => {
+                (self.config.font_size as usize * title.split('\n').count()) as u32 * 11 / 10
+            }
+            None => 0,
+        }
+    }
+
+    /// The left and right edges of the participants in the box, whose centres are at the
+    /// given positions.
+    fn participant_edges(
+        &self,
+        diagram: &SequenceDiagram,
+        first_center: u32,
+        last_center: u32,
+    ) -> (u32, u32) {
+        let participants = diagram.get_participants();
+        (
+            first_center - get_rendered_width(&participants[self.first].borrow()) / 2,
+            last_center + get_rendered_width(&participants[self.last].borrow()) / 2,
+        )
+    }
+
+    /// Returns how much space needs to be added on each side of the participants for the
+    /// title to fit into the box.
+    pub fn title_space(
+        &self,
+        diagram: &SequenceDiagram,
+        first_center: u32,
+        last_center: u32,
+    ) -> (u32, u32) {
+        let (left, right) = self.participant_edges(diagram, first_center, last_center);
+        let missing_width = self.title_width().saturating_sub(right - left);
+        (missing_width / 2, missing_width - missing_width / 2)
+    }
+
+    pub fn draw(
+        &self,
+        diagram: &SequenceDiagram,
+        renderer: &mut dyn Renderer,
+        grid: &GridSize,
+        top: u32,
+    ) {
+        let first_center = grid.get_col_center(self.first);
+        let last_center = grid.get_col_center(self.last);
+        let (left, right) = self.participant_edges(diagram, first_center, last_center);
+        let (left_space, right_space) = self.title_space(diagram, first_center, last_center);
+        let left = left - left_space - self.config.padding;
+        let right = right + right_space + self.config.padding;
+        let bottom = grid.get_row_bottom(grid.num_rows() - 1);
+
+        let params = RectParams {
+            fill: &self.fill,
+            stroke: &self.config.border,
+            stroke_width: 1,
+            ..Default::default()
+        };
+        renderer.render_rect(left, top, right - left, bottom - top, params);
+
+        if let Some(title) = &self.title {
+            renderer.render_text(
+                title,
+                (left + right) / 2,
+                top,
+                &self.config.font,
+                self.config.font_size,
+                "middle",
+            );
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ParticipantBoxConfig {
+    pub font_size: u32,
+    pub font: Font,
+    /// The space between the box's edge and the participants inside it
+    pub padding: u32,
+    pub background: String,
+    pub border: String,
+}
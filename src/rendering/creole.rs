@@ -0,0 +1,241 @@
+//! Parsing of the Creole markup in labels, e.g. `**bold**`, `//italic//`, `""mono""`,
+//! `--strike--`, `__underline__`, `<color:red>`, `<size:18>`, `<b>` and `[[links]]`.
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub monospace: bool,
+    pub strike: bool,
+    pub underline: bool,
+    pub colour: Option<String>,
+    pub font_size: Option<u32>,
+    pub link: Option<String>,
+}
+
+impl TextStyle {
+    pub fn is_plain(&self) -> bool {
+        *self == TextStyle::default()
+    }
+}
+
+/// A piece of text with the same style throughout.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextRun {
+    pub text: String,
+    pub style: TextStyle,
+}
+
+/// The markers that toggle a style on and off, e.g. `**bold**`.
+static TOGGLES: [&str; 5] = ["**", "//", "\"\"", "--", "__"];
+
+fn toggle(style: &mut TextStyle, marker: &str) {
+    match marker {
+        "**" => style.bold = !style.bold,
+        "//" => style.italic = !style.italic,
+        "\"\"" => style.monospace = !style.monospace,
+        "--" => style.strike = !style.strike,
+        _ => style.underline = !style.underline,
+    }
+}
+
+fn is_toggled(style: &TextStyle, marker: &str) -> bool {
+    match marker {
+        "**" => style.bold,
+        "//" => style.italic,
+        "\"\"" => style.monospace,
+        "--" => style.strike,
+        _ => style.underline,
+    }
+}
+
+/// Applies an HTML-like opening tag (without the angle brackets) to the style.
+/// Returns false if the tag isn't supported.
+fn apply_tag(style: &mut TextStyle, tag: &str) -> bool {
+    match tag {
+        "b" => style.bold = true,
+        "i" => style.italic = true,
+        "u" => style.underline = true,
+        "s" | "strike" => style.strike = true,
+        _ => {
+            if let Some(colour) = tag.strip_prefix("color:") {
+                style.colour = Some(colour.trim().to_string());
+            } else if let Some(size) = tag.strip_prefix("size:") {
+                match size.trim().parse() {
+                    Ok(size) => style.font_size = Some(size),
+                    Err(_) => return false,
+                }
+            } else {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Splits a single line of text into runs of text with the same style.
+/// Markup that isn't closed on the line is kept as text, and `~` escapes the next character.
+pub fn parse_line(line: &str) -> Vec<TextRun> {
+    let mut runs = vec![];
+    let mut text = String::new();
+    let mut style = TextStyle::default();
+    // The styles to go back to when the open tags are closed
+    let mut tag_stack: Vec<(String, TextStyle)> = vec![];
+
+    let flush = |runs: &mut Vec<TextRun>, text: &mut String, style: &TextStyle| {
+        if !text.is_empty() {
+            runs.push(TextRun {
+                text: std::mem::take(text),
+                style: style.clone(),
+            });
+        }
+    };
+
+    let mut rest = line;
+    'outer: while let Some(c) = rest.chars().next() {
+        if c == '~' {
+            let mut chars = rest.chars().skip(1);
+            if let Some(escaped) = chars.next() {
+                text.push(escaped);
+                rest = &rest[1 + escaped.len_utf8()..];
+            } else {
+                text.push(c);
+                rest = "";
+            }
+            continue;
+        }
+
+        if let Some(link) = rest.strip_prefix("[[") {
+            if let Some(end) = link.find("]]") {
+                let (url, label) = match link[..end].trim().split_once(' ') {
+                    Some((url, label)) => (url, label.trim()),
+                    None => (link[..end].trim(), link[..end].trim()),
+                };
+                flush(&mut runs, &mut text, &style);
+                let mut link_style = style.clone();
+                link_style.link = Some(url.to_string());
+                runs.push(TextRun {
+                    text: label.to_string(),
+                    style: link_style,
+                });
+                rest = &link[end + 2..];
+                continue;
+            }
+        }
+
+        for marker in TOGGLES.iter() {
+            if let Some(after) = rest.strip_prefix(marker) {
+                // A marker only opens a style if it's closed later on the line
+                if is_toggled(&style, marker) || after.contains(marker) {
+                    flush(&mut runs, &mut text, &style);
+                    toggle(&mut style, marker);
+                    rest = after;
+                    continue 'outer;
+                }
+            }
+        }
+
+        if let Some(tag) = rest.strip_prefix('<') {
+            if let Some(end) = tag.find('>') {
+                let tag_content = tag[..end].trim().to_lowercase();
+                if let Some(closing) = tag_content.strip_prefix('/') {
+                    let name = closing.trim();
+                    if let Some(pos) = tag_stack.iter().rposition(|(open, _)| open == name) {
+                        flush(&mut runs, &mut text, &style);
+                        style = tag_stack[pos].1.clone();
+                        tag_stack.truncate(pos);
+                        rest = &tag[end + 1..];
+                        continue;
+                    }
+                } else {
+                    let mut new_style = style.clone();
+                    if apply_tag(&mut new_style, &tag_content) {
+                        flush(&mut runs, &mut text, &style);
+                        let name = tag_content.split(':').next().unwrap().to_string();
+                        tag_stack.push((name, std::mem::replace(&mut style, new_style)));
+                        rest = &tag[end + 1..];
+                        continue;
+                    }
+                }
+            }
+        }
+
+        text.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    flush(&mut runs, &mut text, &style);
+    runs
+}
+
+/// Writes the runs back as markup that `parse_line` turns into the same runs. The styles
+/// are written as tags, so a line can be split into several lines that each keep their
+/// styles.
+pub fn to_markup(runs: &[TextRun]) -> String {
+    let mut markup = String::new();
+    for run in merge_runs(runs) {
+        let style = &run.style;
+        // The opening tags, along with the names that close them
+        let mut tags = vec![];
+        if let Some(colour) = &style.colour {
+            tags.push((format!("color:{}", colour), "color"));
+        }
+        if let Some(size) = style.font_size {
+            tags.push((format!("size:{}", size), "size"));
+        }
+        for (enabled, tag) in [
+            (style.bold, "b"),
+            (style.italic, "i"),
+            (style.underline, "u"),
+            (style.strike, "s"),
+        ] {
+            if enabled {
+                tags.push((tag.to_string(), tag));
+            }
+        }
+
+        for (tag, _) in &tags {
+            markup.push_str(&format!("<{}>", tag));
+        }
+        let mut text = match &style.link {
+            Some(url) if *url == run.text => format!("[[{}]]", url),
+            Some(url) => format!("[[{} {}]]", url, run.text),
+            None => escape(&run.text),
+        };
+        if style.monospace {
+            text = format!("\"\"{}\"\"", text);
+        }
+        markup.push_str(&text);
+        for (_, name) in tags.iter().rev() {
+            markup.push_str(&format!("</{}>", name));
+        }
+    }
+    markup
+}
+
+/// Joins the neighbouring runs that have the same style.
+fn merge_runs(runs: &[TextRun]) -> Vec<TextRun> {
+    let mut merged: Vec<TextRun> = vec![];
+    for run in runs {
+        match merged.last_mut() {
+            Some(last) if last.style == run.style && run.style.link.is_none() => {
+                last.text.push_str(&run.text)
+            }
+            _ => merged.push(run.clone()),
+        }
+    }
+    merged
+}
+
+/// Escapes the characters of the text that would otherwise be read as markup.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let doubled = "*/\"-_[".contains(c) && chars.peek() == Some(&c);
+        if c == '~' || c == '<' || doubled {
+            escaped.push('~');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,169 @@
+//! Text measurement based on the advance widths of the glyphs in a font.
+//! A few common fonts are bundled as width tables, other fonts can be loaded from TrueType
+//! or OpenType files when the `font-files` feature is enabled.
+
+#[cfg(feature = "font-files")]
+use crate::error::Error;
+#[cfg(feature = "font-files")]
+use std::collections::HashMap;
+use std::rc::Rc;
+use unicode_width::UnicodeWidthChar;
+
+/// The advance widths of the printable ASCII characters from the space to the tilde in
+/// Helvetica, in 1/1000 of the font size.
+static HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, // ' '../
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, // 0..?
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778, // @..O
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556, // P.._
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556, // `..o
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584, // p..~
+];
+
+/// The advance widths of the printable ASCII characters in Times, like `HELVETICA_WIDTHS`.
+static TIMES_WIDTHS: [u16; 95] = [
+    250, 333, 408, 500, 500, 833, 778, 180, 333, 333, 500, 564, 250, 333, 250, 278, // ' '../
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 278, 278, 564, 564, 564, 444, // 0..?
+    921, 722, 667, 667, 722, 611, 556, 722, 722, 333, 389, 722, 611, 889, 722, 722, // @..O
+    556, 722, 667, 556, 611, 722, 722, 944, 722, 722, 611, 333, 278, 333, 469, 500, // P.._
+    333, 444, 500, 444, 500, 444, 333, 500, 500, 278, 278, 500, 278, 778, 500, 500, // `..o
+    500, 500, 333, 389, 278, 500, 500, 722, 500, 500, 444, 480, 200, 480, 541, // p..~
+];
+
+#[derive(Debug)]
+enum Widths {
+    /// Every character has the same width, as in monospace fonts
+    Fixed(u16),
+    /// A table of the printable ASCII characters
+    Ascii(&'static [u16; 95]),
+    /// The widths of all the characters in a font file
+    #[cfg(feature = "font-files")]
+    Loaded(HashMap<char, u16>),
+}
+
+#[derive(Debug)]
+struct FontMetrics {
+    family: String,
+    units_per_em: u32,
+    widths: Widths,
+    /// The width of the characters that are missing from the widths
+    default_width: u16,
+}
+
+/// A font used to render and measure text. Cloning it is cheap as the metrics are shared.
+#[derive(Clone, Debug)]
+pub struct Font {
+    metrics: Rc<FontMetrics>,
+}
+
+impl Font {
+    fn bundled_font(family: &str, widths: Widths, default_width: u16) -> Font {
+        Font {
+            metrics: Rc::new(FontMetrics {
+                family: family.to_string(),
+                units_per_em: 1000,
+                widths,
+                default_width,
+            }),
+        }
+    }
+
+    pub fn courier() -> Font {
+        Font::bundled_font("Courier New", Widths::Fixed(600), 600)
+    }
+
+    pub fn helvetica() -> Font {
+        Font::bundled_font("Helvetica", Widths::Ascii(&HELVETICA_WIDTHS), 556)
+    }
+
+    pub fn times() -> Font {
+        Font::bundled_font("Times New Roman", Widths::Ascii(&TIMES_WIDTHS), 500)
+    }
+
+    /// Returns the bundled font with the given name or generic family, ignoring the case.
+    pub fn bundled(name: &str) -> Option<Font> {
+        match name.trim().to_lowercase().as_str() {
+            "courier" | "courier new" | "monospace" | "monospaced" => Some(Font::courier()),
+            "helvetica" | "arial" | "sansserif" | "sans-serif" => Some(Font::helvetica()),
+            "times" | "times new roman" | "serif" => Some(Font::times()),
+            _ => None,
+        }
+    }
+
+    /// Loads the metrics of a TrueType or OpenType font file.
+    #[cfg(feature = "font-files")]
+    pub fn from_file(path: &str) -> Result<Font, Error> {
+        let data = std::fs::read(path)
+            .map_err(|err| Error::new(format!("Failed to read font {}: {}", path, err)))?;
+        let face = ttf_parser::Face::from_slice(&data, 0)
+            .map_err(|err| Error::new(format!("Failed to parse font {}: {}", path, err)))?;
+
+        let mut widths = HashMap::new();
+        if let Some(cmap) = face.tables().cmap {
+            for subtable in cmap.subtables.into_iter().filter(|s| s.is_unicode()) {
+                subtable.codepoints(|code_point| {
+                    let width = char::from_u32(code_point)
+                        .and_then(|c| Some((c, face.glyph_index(c)?)))
+                        .and_then(|(c, glyph)| Some((c, face.glyph_hor_advance(glyph)?)));
+                    if let Some((c, width)) = width {
+                        widths.insert(c, width);
+                    }
+                });
+            }
+        }
+
+        let family = face
+            .names()
+            .into_iter()
+            .filter(|name| name.name_id == ttf_parser::name_id::FAMILY)
+            .find_map(|name| name.to_string())
+            .unwrap_or_else(|| path.to_string());
+        let units_per_em = face.units_per_em() as u32;
+        let default_width = widths.get(&'0').copied().unwrap_or(units_per_em as u16 / 2);
+        Ok(Font {
+            metrics: Rc::new(FontMetrics {
+                family,
+                units_per_em,
+                widths: Widths::Loaded(widths),
+                default_width,
+            }),
+        })
+    }
+
+    pub fn family(&self) -> &str {
+        &self.metrics.family
+    }
+
+    /// The advance width of a character in font units.
+    fn char_width(&self, c: char) -> u32 {
+        let metrics = &self.metrics;
+        let width = match &metrics.widths {
+            Widths::Fixed(width) if c.is_ascii() => Some(*width),
+            Widths::Ascii(widths) if (' '..='~').contains(&c) => Some(widths[c as usize - 32]),
+            #[cfg(feature = "font-files")]
+            Widths::Loaded(widths) => widths.get(&c).copied(),
+            _ => None,
+        };
+        match (width, c.width()) {
+            (Some(width), _) => width as u32,
+            // Combining marks and control characters take up no space
+            (None, None) | (None, Some(0)) => 0,
+            // Wide characters, e.g. CJK ideographs and emoji, take up a full em
+            (None, Some(2)) => metrics.units_per_em,
+            (None, Some(_)) => metrics.default_width as u32,
+        }
+    }
+
+    /// Returns the width of a line of text without any markup, rounded up.
+    pub fn string_width(&self, s: &str, font_size: u32) -> u32 {
+        let units: u32 = s.chars().map(|c| self.char_width(c)).sum();
+        let units_per_em = self.metrics.units_per_em;
+        (units * font_size).div_ceil(units_per_em)
+    }
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Font::courier()
+    }
+}
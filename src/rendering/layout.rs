@@ -1,5 +1,8 @@
 use crate::diagram::SequenceDiagram;
+use crate::message::{Border, BORDER_MARGIN};
 use crate::participant::get_participant_width;
+use crate::rendering::creole::{parse_line, to_markup, TextRun, TextStyle};
+use crate::rendering::font::Font;
 use itertools::Itertools;
 use std::cmp::Ordering;
 
@@ -9,13 +12,17 @@ static ROW_MARGIN: u32 = 8;
 pub struct GridSize {
     pub cols: Vec<u32>,
     row_bounds: Vec<u32>,
+    bottom_height: u32,
 }
 
 impl GridSize {
-    fn new() -> GridSize {
+    /// Creates a grid whose rows start below `top_height` and which leaves `bottom_height`
+    /// free below the last row, for the diagram's decorations.
+    fn new(top_height: u32, bottom_height: u32) -> GridSize {
         GridSize {
-            row_bounds: vec![ROW_MARGIN],
+            row_bounds: vec![top_height + ROW_MARGIN],
             cols: vec![0],
+            bottom_height,
         }
     }
 
@@ -48,13 +55,20 @@ impl GridSize {
     }
 
     pub fn height(&self) -> u32 {
+        self.timeline_bottom() + self.bottom_height
+    }
+
+    /// The bottom of the last row, below which only the decorations are drawn.
+    pub fn timeline_bottom(&self) -> u32 {
         *self.row_bounds.last().unwrap()
     }
 
-    fn add_row(&mut self, height: u32) {
+    /// Adds a row of the given height, leaving `height_below` free under it in addition to
+    /// the usual margin.
+    fn add_row(&mut self, height: u32, height_below: u32) {
         let bottom = self.row_bounds.last().unwrap() + height;
         self.row_bounds.push(bottom);
-        self.row_bounds.push(bottom + ROW_MARGIN);
+        self.row_bounds.push(bottom + ROW_MARGIN + height_below);
     }
 }
 
@@ -87,19 +101,135 @@ impl ReservedWidth {
 }
 
 pub fn calculate_grid(diagram: &SequenceDiagram) -> GridSize {
-    let mut grid = GridSize::new();
+    let decorations = diagram.get_decorations();
+    let box_title_height = diagram
+        .get_boxes()
+        .iter()
+        .map(|participant_box| participant_box.title_height())
+        .max()
+        .unwrap_or(0);
+    let mut grid = GridSize::new(
+        decorations.top_height() + box_title_height,
+        decorations.bottom_height(),
+    );
     for events in diagram.get_timeline() {
-        let height = events.iter().map(|ev| ev.height()).max();
-        grid.add_row(height.unwrap());
+        // The participant row is empty when every participant is created later on
+        let height = events.iter().map(|ev| ev.height()).max().unwrap_or(0);
+        let height_below = events.iter().map(|ev| ev.height_below()).max().unwrap_or(0);
+        grid.add_row(height, height_below);
+    }
+    if diagram.shows_footbox() {
+        // The participants are drawn again at the bottom in a row of their own, which has
+        // to fit the created participants as well as those in the first row
+        let footbox_height = diagram
+            .get_timeline()
+            .iter()
+            .flatten()
+            .filter(|ev| ev.drawn_participant().is_some())
+            .map(|ev| ev.height())
+            .max()
+            .unwrap_or(0);
+        grid.add_row(footbox_height, 0);
     }
-    grid.add_row(grid.get_row_height(0));
 
     grid.cols = calculate_cols(diagram);
     grid
 }
 
-pub fn string_width(s: &str, font_size: u32) -> u32 {
-    s.len() as u32 * font_size * 9 / 14
+/// Returns the width of a line of text, measuring each styled run of the Creole markup
+/// with its own font size. Monospaced runs are measured with the monospace font.
+pub fn string_width(s: &str, font: &Font, font_size: u32) -> u32 {
+    parse_line(s)
+        .iter()
+        .map(|run| {
+            let run_font_size = run.style.font_size.unwrap_or(font_size);
+            if run.style.monospace {
+                Font::courier().string_width(&run.text, run_font_size)
+            } else {
+                font.string_width(&run.text, run_font_size)
+            }
+        })
+        .sum()
+}
+
+/// Returns the width of the longest line of a multiline text.
+pub fn text_width(text: &str, font: &Font, font_size: u32) -> u32 {
+    text.split('\n')
+        .map(|line| string_width(line, font, font_size))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Returns the height of a multiline text, from the top of its first line to the bottom
+/// of its last one.
+pub fn text_height(text: &str, font_size: u32) -> u32 {
+    let lines = text.split('\n').count() as u32;
+    font_size + (lines - 1) * font_size * 11 / 10
+}
+
+/// Word-wraps the text so that none of its lines is wider than `max_width`. Words that
+/// are wider than that on their own are kept on a line of their own. The markup is
+/// wrapped as styled runs, so that the styles carry over to the next line.
+pub fn wrap_text(text: &str, max_width: u32, font: &Font, font_size: u32) -> String {
+    let mut lines = vec![];
+    for paragraph in text.split('\n') {
+        // Lines that fit are kept as written rather than rewritten from their runs
+        if string_width(paragraph, font, font_size) <= max_width {
+            lines.push(paragraph.to_string());
+            continue;
+        }
+        let mut line: Vec<TextRun> = vec![];
+        for (space_style, word) in split_words(&parse_line(paragraph)) {
+            if !line.is_empty() {
+                let mut wider_line = line.clone();
+                wider_line.push(TextRun {
+                    text: " ".to_string(),
+                    style: space_style,
+                });
+                wider_line.extend(word.iter().cloned());
+                if string_width(&to_markup(&wider_line), font, font_size) <= max_width {
+                    line = wider_line;
+                    continue;
+                }
+                lines.push(to_markup(&line));
+            }
+            line = word;
+        }
+        lines.push(to_markup(&line));
+    }
+    lines.join("\n")
+}
+
+/// Splits the runs of a line into words, each paired with the style of the space in
+/// front of it. Words may be made up of several runs, and links are never split.
+fn split_words(runs: &[TextRun]) -> Vec<(TextStyle, Vec<TextRun>)> {
+    let mut words = vec![];
+    let mut space_style = TextStyle::default();
+    let mut word = vec![];
+    for run in runs {
+        if run.style.link.is_some() {
+            word.push(run.clone());
+            continue;
+        }
+        for (i, part) in run.text.split(' ').enumerate() {
+            if i > 0 {
+                if !word.is_empty() {
+                    words.push((space_style, std::mem::take(&mut word)));
+                }
+                space_style = run.style.clone();
+            }
+            if !part.is_empty() {
+                word.push(TextRun {
+                    text: part.to_string(),
+                    style: run.style.clone(),
+                });
+            }
+        }
+    }
+    if !word.is_empty() {
+        words.push((space_style, word));
+    }
+    words
 }
 
 fn calculate_cols(diagram: &SequenceDiagram) -> Vec<u32> {
@@ -122,6 +252,33 @@ fn calculate_cols(diagram: &SequenceDiagram) -> Vec<u32> {
         }
     }
 
+    // Make room for the messages from and to the borders of the diagram
+    let borders: Vec<Border> = diagram
+        .get_timeline()
+        .iter()
+        .flatten()
+        .filter_map(|ev| ev.message()?.border())
+        .collect();
+    if borders.iter().any(|border| border.is_left()) {
+        for col in &mut cols[1..] {
+            *col += BORDER_MARGIN;
+        }
+    }
+    if borders.iter().any(|border| !border.is_left()) {
+        *cols.last_mut().unwrap() += BORDER_MARGIN;
+    }
+
+    // Make room for the padding on both sides of the boxes
+    for participant_box in diagram.get_boxes() {
+        let padding = participant_box.config.padding;
+        for col in &mut cols[participant_box.first + 1..] {
+            *col += padding * 2;
+        }
+        for col in &mut cols[participant_box.last + 2..] {
+            *col += padding * 2;
+        }
+    }
+
     fn cmp(rw1: &ReservedWidth, rw2: &ReservedWidth) -> Ordering {
         match rw1.col_distance().cmp(&rw2.col_distance()) {
             Ordering::Equal => rw1.left_col.cmp(&rw2.left_col),
@@ -132,8 +289,7 @@ fn calculate_cols(diagram: &SequenceDiagram) -> Vec<u32> {
         .get_timeline()
         .iter()
         .flatten()
-        .map(|ev| ev.reserved_width())
-        .flatten()
+        .filter_map(|ev| ev.reserved_width())
         .sorted_by(cmp);
 
     for rw in reserved_widths {
@@ -146,5 +302,22 @@ fn calculate_cols(diagram: &SequenceDiagram) -> Vec<u32> {
             }
         }
     }
+
+    for participant_box in diagram.get_boxes() {
+        let (left_space, right_space) = participant_box.title_space(
+            diagram,
+            cols[participant_box.first + 1],
+            cols[participant_box.last + 1],
+        );
+        for col in &mut cols[participant_box.first + 1..] {
+            *col += left_space;
+        }
+        for col in &mut cols[participant_box.last + 2..] {
+            *col += right_space;
+        }
+    }
+
+    let last = cols.len() - 1;
+    cols[last] = cols[last].max(diagram.get_decorations().width());
     cols
 }
@@ -1,3 +1,5 @@
+pub mod creole;
+pub mod font;
 pub mod layout;
 pub mod renderer;
 
@@ -13,12 +15,20 @@ pub fn render(diagram: &SequenceDiagram, show_debug_lines: bool) -> String {
     let height = grid_size.height();
     let mut renderer = SVGRenderer::new(width, height);
 
+    // Boxes are drawn first to stay behind the participants and their lifelines
+    let boxes_top = diagram.get_decorations().top_height();
+    for participant_box in diagram.get_boxes() {
+        participant_box.draw(diagram, &mut renderer, &grid_size, boxes_top);
+    }
+
     for (row_idx, row) in diagram.get_timeline().iter().enumerate() {
         for event in row {
             event.draw(diagram, &mut renderer, &grid_size, row_idx);
         }
     }
 
+    diagram.get_decorations().draw(&mut renderer, &grid_size);
+
     if show_debug_lines {
         render_debug_lines(&mut renderer, &grid_size);
     }
@@ -1,9 +1,19 @@
+use crate::rendering::creole::{parse_line, TextStyle};
+use crate::rendering::font::Font;
 use nalgebra::Point2;
 use svg::node::element::{Circle, Definitions, Element, Line, Marker, Path, Rectangle, Text};
 use svg::node::{Node, Text as TextNode};
 use svg::Document;
 
 static ARROW_HEAD_ID: &str = "arrow";
+static THIN_ARROW_HEAD_ID: &str = "arrow-thin";
+static CROSS_ARROW_HEAD_ID: &str = "arrow-cross";
+static HALF_UPPER_ARROW_HEAD_ID: &str = "arrow-half-upper";
+static HALF_LOWER_ARROW_HEAD_ID: &str = "arrow-half-lower";
+static THIN_HALF_UPPER_ARROW_HEAD_ID: &str = "arrow-thin-half-upper";
+static THIN_HALF_LOWER_ARROW_HEAD_ID: &str = "arrow-thin-half-lower";
+static ARROW_CIRCLE_RADIUS: u32 = 4;
+pub static DEFAULT_ARROW_COLOUR: &str = "black";
 
 pub static LIGHT_BLUE: &str = "#add3ff";
 pub static MEDIUM_BLUE: &str = "#62acff";
@@ -14,17 +24,122 @@ pub static MEDIUM_PURPLE: &str = "#ae8ccf";
 pub static LIGHT_GREY: &str = "#dedede";
 pub static DARK_GREY: &str = "#383838";
 
-#[derive(PartialEq, Debug, Clone)]
+pub static DARK_RED: &str = "#a80036";
+
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum LineStyle {
     Plain,
     Dashed,
+    Dotted,
+    /// The arrow takes up space, but isn't drawn
+    Hidden,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ArrowHead {
+    None,
+    Filled,
+    Thin,
+    Cross,
+    HalfUpper,
+    HalfLower,
+    ThinHalfUpper,
+    ThinHalfLower,
+}
+
+impl ArrowHead {
+    /// Returns the head drawn when the arrow points the other way, which only differs
+    /// for half heads as they are defined relative to the top of the diagram.
+    fn mirrored(self) -> ArrowHead {
+        match self {
+            ArrowHead::HalfUpper => ArrowHead::HalfLower,
+            ArrowHead::HalfLower => ArrowHead::HalfUpper,
+            ArrowHead::ThinHalfUpper => ArrowHead::ThinHalfLower,
+            ArrowHead::ThinHalfLower => ArrowHead::ThinHalfUpper,
+            head => head,
+        }
+    }
+
+    /// Returns the ID of the marker drawing the head in the given colour.
+    fn marker_id(self, colour: &str) -> Option<String> {
+        let id = match self {
+            ArrowHead::None => return None,
+            ArrowHead::Filled => ARROW_HEAD_ID,
+            ArrowHead::Thin => THIN_ARROW_HEAD_ID,
+            ArrowHead::Cross => CROSS_ARROW_HEAD_ID,
+            ArrowHead::HalfUpper => HALF_UPPER_ARROW_HEAD_ID,
+            ArrowHead::HalfLower => HALF_LOWER_ARROW_HEAD_ID,
+            ArrowHead::ThinHalfUpper => THIN_HALF_UPPER_ARROW_HEAD_ID,
+            ArrowHead::ThinHalfLower => THIN_HALF_LOWER_ARROW_HEAD_ID,
+        };
+        Some(marker_id(id, colour))
+    }
+}
+
+/// The decoration at one end of an arrow.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct ArrowEnd {
+    pub head: ArrowHead,
+    pub circle: bool,
+}
+
+impl ArrowEnd {
+    pub fn new(head: ArrowHead, circle: bool) -> ArrowEnd {
+        ArrowEnd { head, circle }
+    }
+
+    /// Returns an arrow end with no decoration at all.
+    pub fn plain() -> ArrowEnd {
+        ArrowEnd::new(ArrowHead::None, false)
+    }
+
+    pub fn is_plain(&self) -> bool {
+        self.head == ArrowHead::None && !self.circle
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Arrow {
+    pub style: LineStyle,
+    pub start: ArrowEnd,
+    pub end: ArrowEnd,
+    pub colour: String,
+    pub thickness: u8,
+}
+
+impl Arrow {
+    pub fn new(style: LineStyle, start: ArrowEnd, end: ArrowEnd) -> Arrow {
+        Arrow {
+            style,
+            start,
+            end,
+            colour: DEFAULT_ARROW_COLOUR.to_string(),
+            thickness: 1,
+        }
+    }
+
+    pub fn dash(&self) -> u8 {
+        match self.style {
+            LineStyle::Plain | LineStyle::Hidden => 0,
+            LineStyle::Dashed => 5,
+            LineStyle::Dotted => 2,
+        }
+    }
 }
 
 pub trait Renderer {
     fn render_rect(&mut self, x: u32, y: u32, width: u32, height: u32, params: RectParams);
-    fn render_circle(&mut self, center: Point2<u32>, r: u32, stroke_colour: &str);
-    fn render_text(&mut self, text: &str, x: u32, y: u32, font_size: u32, text_anchor: &str);
-    fn render_arrow(&mut self, p1: Point2<u32>, p2: Point2<u32>, dash: u8);
+    fn render_outlined_circle(&mut self, center: Point2<u32>, r: u32, fill: &str, stroke: &str);
+    fn render_text(
+        &mut self,
+        text: &str,
+        x: u32,
+        y: u32,
+        font: &Font,
+        font_size: u32,
+        text_anchor: &str,
+    );
+    fn render_arrow(&mut self, p1: Point2<u32>, p2: Point2<u32>, arrow: &Arrow);
     fn render_line(
         &mut self,
         p1: Point2<u32>,
@@ -34,8 +149,9 @@ pub trait Renderer {
         stroke_colour: &str,
         marker_end: Option<&str>,
     );
-    fn render_db_icon(&mut self, x: u32, y: u32, width: u32, height: u32);
-    fn render_stickman(&mut self, x: u32, y: u32, width: u32, height: u32);
+    fn render_db_icon(&mut self, x: u32, y: u32, width: u32, height: u32, params: RectParams);
+    fn render_stickman(&mut self, x: u32, y: u32, width: u32, height: u32, params: RectParams);
+    fn render_queue_icon(&mut self, x: u32, y: u32, width: u32, height: u32, params: RectParams);
     fn render_note_box(
         &mut self,
         x: u32,
@@ -45,29 +161,22 @@ pub trait Renderer {
         fill: &str,
         stroke: &str,
     );
+    fn render_hexagon(&mut self, x: u32, y: u32, width: u32, height: u32, fill: &str, stroke: &str);
 }
 
 pub struct SVGRenderer {
     doc: Document,
+    /// The colours that arrow head markers have been defined for
+    marker_colours: Vec<String>,
 }
 
 impl SVGRenderer {
     pub fn new(width: u32, height: u32) -> SVGRenderer {
-        let path = Path::new().set("d", "M0,0 L0,8 L9,4 z");
-        let marker = Marker::new()
-            .set("id", ARROW_HEAD_ID)
-            .set("markerWidth", 10)
-            .set("markerHeight", 10)
-            .set("markerUnits", "userSpaceOnUse")
-            .set("refX", 9)
-            .set("refY", 4)
-            .set("orient", "auto")
-            .add(path);
-
         SVGRenderer {
             doc: Document::new()
                 .set("viewBox", (-5, -5, width + 10, height + 10))
-                .add(Definitions::new().add(marker)),
+                .add(arrow_head_markers(DEFAULT_ARROW_COLOUR)),
+            marker_colours: vec![DEFAULT_ARROW_COLOUR.to_string()],
         }
     }
 
@@ -75,6 +184,14 @@ impl SVGRenderer {
         self.doc.to_string()
     }
 
+    /// Defines the arrow head markers in the given colour unless they already exist.
+    fn ensure_markers(&mut self, colour: &str) {
+        if !self.marker_colours.iter().any(|c| c == colour) {
+            self.marker_colours.push(colour.to_string());
+            self.add(arrow_head_markers(colour));
+        }
+    }
+
     fn add<T>(&mut self, node: T)
     where
         T: Node,
@@ -99,22 +216,31 @@ impl Renderer for SVGRenderer {
         self.add(rect);
     }
 
-    fn render_circle(&mut self, center: Point2<u32>, r: u32, stroke_colour: &str) {
+    fn render_outlined_circle(&mut self, center: Point2<u32>, r: u32, fill: &str, stroke: &str) {
         let circle = Circle::new()
             .set("cx", center.x)
             .set("cy", center.y)
             .set("r", r)
-            .set("fill", stroke_colour)
-            .set("stroke", stroke_colour);
+            .set("fill", fill)
+            .set("stroke", stroke)
+            .set("stroke-width", 2);
         self.add(circle);
     }
 
-    fn render_text(&mut self, text: &str, x: u32, y: u32, font_size: u32, text_anchor: &str) {
+    fn render_text(
+        &mut self,
+        text: &str,
+        x: u32,
+        y: u32,
+        font: &Font,
+        font_size: u32,
+        text_anchor: &str,
+    ) {
         let lines = text.split('\n');
         let mut text = Text::new()
             .set("x", x)
             .set("y", y)
-            .set("font-family", "Courier New")
+            .set("font-family", font.family())
             .set("font-size", font_size)
             .set("text-anchor", text_anchor);
 
@@ -123,15 +249,69 @@ impl Renderer for SVGRenderer {
             let mut tspan = Element::new("tspan");
             tspan.assign("x", x);
             tspan.assign("dy", line_height);
-            tspan.append(TextNode::new(line));
+            let runs = parse_line(line);
+            if runs.is_empty() {
+                tspan.append(TextNode::new(""));
+            }
+            for run in runs {
+                if run.style.is_plain() {
+                    tspan.append(TextNode::new(run.text));
+                } else {
+                    tspan.append(styled_tspan(&run.text, &run.style));
+                }
+            }
             text = text.add(tspan);
         }
 
         self.add(text);
     }
 
-    fn render_arrow(&mut self, p1: Point2<u32>, p2: Point2<u32>, dash: u8) {
-        self.render_line(p1, p2, 1, dash, "black", Some(ARROW_HEAD_ID));
+    fn render_arrow(&mut self, p1: Point2<u32>, p2: Point2<u32>, arrow: &Arrow) {
+        // Markers are oriented along the line, so half heads need to be mirrored when the
+        // marker points to the left to keep them on the same side of the line.
+        let oriented = |head: ArrowHead, points_left: bool| {
+            if points_left {
+                head.mirrored()
+            } else {
+                head
+            }
+        };
+        let start_head = oriented(arrow.start.head, p1.x < p2.x);
+        let end_head = oriented(arrow.end.head, p2.x < p1.x);
+        self.ensure_markers(&arrow.colour);
+
+        let mut line = Line::new()
+            .set("x1", p1.x)
+            .set("y1", p1.y)
+            .set("x2", p2.x)
+            .set("y2", p2.y)
+            .set("stroke", arrow.colour.as_str())
+            .set("stroke-width", arrow.thickness)
+            .set("stroke-dasharray", arrow.dash());
+        if let Some(m) = start_head.marker_id(&arrow.colour) {
+            line = line.set("marker-start", format!("url(#{})", m));
+        }
+        if let Some(m) = end_head.marker_id(&arrow.colour) {
+            line = line.set("marker-end", format!("url(#{})", m));
+        }
+        self.add(line);
+
+        // Circles are drawn just outside the end of the line
+        for (end, from, to) in [(arrow.start, p2, p1), (arrow.end, p1, p2)] {
+            if end.circle {
+                let x = if from.x <= to.x {
+                    to.x + ARROW_CIRCLE_RADIUS
+                } else {
+                    to.x.saturating_sub(ARROW_CIRCLE_RADIUS)
+                };
+                self.render_outlined_circle(
+                    Point2::new(x, to.y),
+                    ARROW_CIRCLE_RADIUS,
+                    "white",
+                    &arrow.colour,
+                );
+            }
+        }
     }
 
     fn render_line(
@@ -157,7 +337,7 @@ impl Renderer for SVGRenderer {
         self.add(line);
     }
 
-    fn render_db_icon(&mut self, x: u32, y: u32, width: u32, height: u32) {
+    fn render_db_icon(&mut self, x: u32, y: u32, width: u32, height: u32, params: RectParams) {
         let x = x as i32;
         let y = y as i32;
         let width = width as i32;
@@ -194,13 +374,13 @@ impl Renderer for SVGRenderer {
         );
         let path = Path::new()
             .set("d", d)
-            .set("stroke", MEDIUM_BLUE)
+            .set("stroke", params.stroke)
             .set("stroke-width", 3)
-            .set("fill", LIGHT_BLUE);
+            .set("fill", params.fill);
         self.add(path);
     }
 
-    fn render_stickman(&mut self, x: u32, y: u32, width: u32, height: u32) {
+    fn render_stickman(&mut self, x: u32, y: u32, width: u32, height: u32, params: RectParams) {
         let x_offset = width / 2;
         let third_height = height / 3;
 
@@ -227,13 +407,49 @@ impl Renderer for SVGRenderer {
             ), // right arm
         ];
         for line in lines {
-            self.render_line(line.0, line.1, 2, 0, MEDIUM_BLUE, None);
+            self.render_line(line.0, line.1, 2, 0, params.stroke, None);
         }
-        self.render_circle(
+        self.render_outlined_circle(
             Point2::new(x, y - height / 6 * 5),
             third_height / 2,
-            MEDIUM_BLUE,
+            params.fill,
+            params.stroke,
+        );
+    }
+
+    fn render_queue_icon(&mut self, x: u32, y: u32, width: u32, height: u32, params: RectParams) {
+        let x = x as i32;
+        let y = y as i32;
+        let width = width as i32;
+        let height = height as i32;
+        let rx = height / 6;
+        let ry = height / 2;
+        let body_width = width - 2 * rx;
+
+        let d = format!(
+            "M {} {} h {} a {} {} 0 0 1 0 {} h {} a {} {} 0 0 1 0 {} z m {} 0 a {} {} 0 0 0 0 {}",
+            x - width / 2 + rx, // top left, after the left end's curve
+            y,
+            body_width, // moving to top right
+            rx,
+            ry,
+            height, // curving down the right end
+            -body_width,
+            rx,
+            ry,
+            -height, // curving up the left end
+            // z moves it back to the starting point
+            body_width, // moving to the top right again
+            rx,
+            ry,
+            height, // the visible inner curve of the right end
         );
+        let path = Path::new()
+            .set("d", d)
+            .set("stroke", params.stroke)
+            .set("stroke-width", 2)
+            .set("fill", params.fill);
+        self.add(path);
     }
 
     fn render_note_box(
@@ -271,6 +487,186 @@ impl Renderer for SVGRenderer {
             .set("fill", fill);
         self.add(path);
     }
+
+    fn render_hexagon(
+        &mut self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        fill: &str,
+        stroke: &str,
+    ) {
+        let x = x as i32;
+        let y = y as i32;
+        let width = width as i32;
+        let height = height as i32;
+        let corner_size = 8;
+
+        let d = format!(
+            "M {} {} l {} {} h {} l {} {} l {} {} h {} z",
+            x, // left corner
+            y + height / 2,
+            corner_size, // moving to top left
+            -height / 2,
+            width - 2 * corner_size, // moving to top right
+            corner_size,             // moving to right corner
+            height / 2,
+            -corner_size, // moving to bottom right
+            height / 2,
+            -(width - 2 * corner_size), // moving to bottom left, z closes the path
+        );
+
+        let path = Path::new()
+            .set("d", d)
+            .set("stroke", stroke)
+            .set("stroke-width", 2)
+            .set("fill", fill);
+        self.add(path);
+    }
+}
+
+/// Creates the tspan for a run of styled text, wrapped in a link if the style has one.
+fn styled_tspan(text: &str, style: &TextStyle) -> Element {
+    let mut tspan = Element::new("tspan");
+    if style.bold {
+        tspan.assign("font-weight", "bold");
+    }
+    if style.italic {
+        tspan.assign("font-style", "italic");
+    }
+    if style.monospace {
+        tspan.assign("font-family", "monospace");
+    }
+    let mut decorations = vec![];
+    if style.underline || style.link.is_some() {
+        decorations.push("underline");
+    }
+    if style.strike {
+        decorations.push("line-through");
+    }
+    if !decorations.is_empty() {
+        tspan.assign("text-decoration", decorations.join(" "));
+    }
+    match (&style.colour, &style.link) {
+        (Some(colour), _) => tspan.assign("fill", parse_colour(colour)),
+        (None, Some(_)) => tspan.assign("fill", "blue"),
+        (None, None) => {}
+    }
+    if let Some(font_size) = style.font_size {
+        tspan.assign("font-size", font_size);
+    }
+    tspan.append(TextNode::new(text));
+
+    match &style.link {
+        Some(url) => {
+            let mut link = Element::new("a");
+            link.assign("href", url.as_str());
+            link.append(tspan);
+            link
+        }
+        None => tspan,
+    }
+}
+
+/// Converts a colour from the diagram DSL (e.g. `#lightgreen` or `#FFAAAA`) to an SVG colour.
+/// Hexadecimal colours keep their leading #, whereas named colours don't have one in SVG.
+pub fn parse_colour(colour: &str) -> String {
+    let value = colour.trim_start_matches('#');
+    let is_hex =
+        (value.len() == 3 || value.len() == 6) && value.chars().all(|c| c.is_ascii_hexdigit());
+    if is_hex {
+        format!("#{}", value)
+    } else {
+        value.to_lowercase()
+    }
+}
+
+/// Returns the ID of a marker in the given colour. The markers in the default colour keep
+/// the plain IDs.
+fn marker_id(id: &str, colour: &str) -> String {
+    if colour == DEFAULT_ARROW_COLOUR {
+        return id.to_string();
+    }
+    let colour_id: String = colour
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect();
+    format!("{}-{}", id, colour_id)
+}
+
+/// Returns the definitions of all the arrow head markers in the given colour.
+fn arrow_head_markers(colour: &str) -> Definitions {
+    Definitions::new()
+        .add(arrow_head_marker(
+            ARROW_HEAD_ID,
+            "M0,0 L0,8 L9,4 z",
+            9,
+            true,
+            colour,
+        ))
+        .add(arrow_head_marker(
+            THIN_ARROW_HEAD_ID,
+            "M0,0 L9,4 L0,8",
+            9,
+            false,
+            colour,
+        ))
+        .add(arrow_head_marker(
+            CROSS_ARROW_HEAD_ID,
+            "M1,0 L9,8 M1,8 L9,0",
+            5,
+            false,
+            colour,
+        ))
+        .add(arrow_head_marker(
+            HALF_UPPER_ARROW_HEAD_ID,
+            "M0,0 L9,4 L0,4 z",
+            9,
+            true,
+            colour,
+        ))
+        .add(arrow_head_marker(
+            HALF_LOWER_ARROW_HEAD_ID,
+            "M0,8 L9,4 L0,4 z",
+            9,
+            true,
+            colour,
+        ))
+        .add(arrow_head_marker(
+            THIN_HALF_UPPER_ARROW_HEAD_ID,
+            "M0,0 L9,4",
+            9,
+            false,
+            colour,
+        ))
+        .add(arrow_head_marker(
+            THIN_HALF_LOWER_ARROW_HEAD_ID,
+            "M0,8 L9,4",
+            9,
+            false,
+            colour,
+        ))
+}
+
+fn arrow_head_marker(id: &str, d: &str, ref_x: u32, filled: bool, colour: &str) -> Marker {
+    let path = if filled {
+        Path::new().set("d", d).set("fill", colour)
+    } else {
+        Path::new()
+            .set("d", d)
+            .set("fill", "none")
+            .set("stroke", colour)
+    };
+    Marker::new()
+        .set("id", marker_id(id, colour))
+        .set("markerWidth", 10)
+        .set("markerHeight", 10)
+        .set("markerUnits", "userSpaceOnUse")
+        .set("refX", ref_x)
+        .set("refY", 4)
+        .set("orient", "auto-start-reverse")
+        .add(path)
 }
 
 pub struct RectParams<'a> {
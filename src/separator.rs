@@ -1,6 +1,7 @@
 use crate::diagram::{SequenceDiagram, TimelineEvent};
-use crate::rendering::layout::{string_width, GridSize, ReservedWidth};
-use crate::rendering::renderer::{RectParams, Renderer, LIGHT_PURPLE, MEDIUM_PURPLE};
+use crate::rendering::font::Font;
+use crate::rendering::layout::{text_height, text_width, GridSize, ReservedWidth};
+use crate::rendering::renderer::{RectParams, Renderer};
 use nalgebra::Point2;
 
 pub struct Separator {
@@ -14,7 +15,7 @@ impl Separator {
     }
 
     pub fn width(&self) -> u32 {
-        string_width(&self.label, self.config.font_size) * 12 / 10
+        text_width(&self.label, &self.config.font, self.config.font_size) * 12 / 10
     }
 }
 
@@ -31,15 +32,15 @@ impl TimelineEvent for Separator {
                 Point2::new(grid.width(), *y),
                 1,
                 0,
-                MEDIUM_PURPLE,
+                &self.config.border,
                 None,
             );
         }
 
         // Draw the box around the label
         let params = RectParams {
-            stroke: MEDIUM_PURPLE,
-            fill: LIGHT_PURPLE,
+            stroke: &self.config.border,
+            fill: &self.config.background,
             ..Default::default()
         };
         let x = (grid.width() - self.width()) / 2;
@@ -50,17 +51,18 @@ impl TimelineEvent for Separator {
             &self.label,
             grid.width() / 2,
             top,
+            &self.config.font,
             self.config.font_size,
             "middle",
         );
     }
 
     fn reserved_width(&self) -> Option<ReservedWidth> {
-        Some(ReservedWidth::new(0, usize::max_value(), self.width() + 10))
+        Some(ReservedWidth::new(0, usize::MAX, self.width() + 10))
     }
 
     fn height(&self) -> u32 {
-        self.config.font_size * 12 / 10
+        text_height(&self.label, self.config.font_size) + self.config.font_size / 5
     }
 
     fn col_range(&self) -> Option<(usize, usize)> {
@@ -68,7 +70,10 @@ impl TimelineEvent for Separator {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct SeparatorConfig {
     pub font_size: u32,
+    pub font: Font,
+    pub background: String,
+    pub border: String,
 }
@@ -0,0 +1,88 @@
+use crate::config::Config;
+use crate::rendering::font::Font;
+use crate::rendering::renderer::parse_colour;
+
+/// Applies a `skinparam` setting to the config. Keys are case insensitive and the
+/// `sequence` prefix is optional, like in PlantUML.
+/// Returns a warning if the key is unknown or the value is invalid.
+pub fn apply_skinparam(config: &mut Config, key: &str, value: &str) -> Result<(), String> {
+    let lowercase_key = key.to_lowercase();
+    let normalized_key = match lowercase_key.strip_prefix("sequence") {
+        Some(rest) if !rest.is_empty() => rest,
+        _ => &lowercase_key,
+    };
+    let invalid = || format!("Invalid value for skinparam {}: {}", key, value);
+    let number = || value.parse::<u32>().map_err(|_| invalid());
+    let flag = || match value.to_lowercase().as_str() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(invalid()),
+    };
+    let colour = || parse_colour(value);
+    let font = || find_font(value);
+
+    match normalized_key {
+        "defaultfontsize" => config.set_font_size(number()?),
+        "defaultfontname" => config.set_font(font()?),
+        "arrowfontsize" => config.message_config.font_size = number()?,
+        "arrowfontname" => config.message_config.font = font()?,
+        "maxmessagesize" => config.message_config.max_message_width = Some(number()?),
+        "responsemessagebelowarrow" => config.message_config.response_below_arrow = flag()?,
+        "style" => match value.to_lowercase().as_str() {
+            "strictuml" => config.message_config.strict_uml = true,
+            _ => return Err(invalid()),
+        },
+        "participantfontsize" => config.participant_config.font_size = number()?,
+        "participantfontname" => config.participant_config.font = font()?,
+        "participantpadding" => config.participant_config.padding = number()?,
+        "participantbackgroundcolor" => config.participant_config.background = colour(),
+        "participantbordercolor" => config.participant_config.border = colour(),
+        "lifelinebordercolor" => config.participant_config.lifeline_colour = colour(),
+        "lifelinestrategy" => {
+            config.participant_config.lifeline_dashed = match value.to_lowercase().as_str() {
+                "solid" => false,
+                "nosolid" => true,
+                _ => return Err(invalid()),
+            }
+        }
+        "notefontsize" => config.note_config.font_size = number()?,
+        "notefontname" => config.note_config.font = font()?,
+        "maxnotesize" => config.note_config.max_note_width = Some(number()?),
+        "notebackgroundcolor" => config.note_config.background = colour(),
+        "notebordercolor" => config.note_config.border = colour(),
+        "groupfontsize" | "groupheaderfontsize" => config.group_config.font_size = number()?,
+        "groupfontname" | "groupheaderfontname" => config.group_config.font = font()?,
+        "groupbackgroundcolor" => config.group_config.background = colour(),
+        "groupbordercolor" => config.group_config.border = colour(),
+        "dividerfontsize" => config.separator_config.font_size = number()?,
+        "dividerfontname" => config.separator_config.font = font()?,
+        "dividerbackgroundcolor" => config.separator_config.background = colour(),
+        "dividerbordercolor" => config.separator_config.border = colour(),
+        "delayfontsize" => config.delay_config.font_size = number()?,
+        "delayfontname" => config.delay_config.font = font()?,
+        "boxfontsize" => config.participant_box_config.font_size = number()?,
+        "boxfontname" => config.participant_box_config.font = font()?,
+        "boxpadding" => config.participant_box_config.padding = number()?,
+        "boxbackgroundcolor" => config.participant_box_config.background = colour(),
+        "boxbordercolor" => config.participant_box_config.border = colour(),
+        _ => return Err(format!("Unknown skinparam: {}", key)),
+    }
+    Ok(())
+}
+
+/// Returns the bundled font with the given name. With the `font-files` feature, a font
+/// that isn't bundled is loaded from the file at the given path instead.
+fn find_font(name: &str) -> Result<Font, String> {
+    let name = name.trim_matches('"');
+    if let Some(font) = Font::bundled(name) {
+        return Ok(font);
+    }
+    #[cfg(feature = "font-files")]
+    {
+        Font::from_file(name).map_err(|err| err.to_string())
+    }
+    #[cfg(not(feature = "font-files"))]
+    {
+        Err(format!("Unknown font: {}", name))
+    }
+}
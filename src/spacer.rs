@@ -0,0 +1,30 @@
+use crate::diagram::{SequenceDiagram, TimelineEvent};
+use crate::rendering::layout::GridSize;
+use crate::rendering::renderer::Renderer;
+
+pub const DEFAULT_SPACER_HEIGHT: u32 = 20;
+
+/// Empty vertical space in the diagram.
+pub struct Spacer {
+    height: u32,
+}
+
+impl Spacer {
+    pub(crate) fn new(height: Option<u32>) -> Spacer {
+        Spacer {
+            height: height.unwrap_or(DEFAULT_SPACER_HEIGHT),
+        }
+    }
+}
+
+impl TimelineEvent for Spacer {
+    fn draw(&self, _: &SequenceDiagram, _: &mut dyn Renderer, _: &GridSize, _: usize) {}
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn col_range(&self) -> Option<(usize, usize)> {
+        None
+    }
+}
@@ -37,6 +37,47 @@ fn participants() {
     assert_node_count(value, 0);
 }
 
+#[test]
+fn participant_kinds() {
+    let diagram_str = "
+    @startuml
+    boundary Boundary
+    control Control
+    entity Entity
+    collections Collections
+    queue Queue
+    controller -> Queue
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    for label in &[
+        "Boundary",
+        "Control",
+        "Entity",
+        "Collections",
+        "Queue",
+        "controller",
+    ] {
+        let value = find_text(&document, label);
+        assert_node_count(value, 2);
+    }
+
+    let tiny_font = "
+    @startuml
+    skinparam ParticipantFontSize 1
+    boundary B
+    control C
+    entity E
+    B -> C
+    C -> E
+    @enduml";
+    assert!(seq_rs::parse(tiny_font, false).is_ok());
+}
+
 #[test]
 fn messages() {
     let diagram_str = "
@@ -64,6 +105,1074 @@ fn messages() {
     }
 }
 
+#[test]
+fn arrow_heads() {
+    let diagram_str = "
+    @startuml
+    a ->> b: Thin
+    a ->x b: Lost
+    a o-> b: Found
+    a <-> b: Both
+    a -\\ b: Upper half
+    a -// b: Lower thin half
+    a -->o b: Circle
+    a <<-- b: Reverse thin
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    for (marker, count) in &[
+        ("arrow", 3),
+        ("arrow-thin", 2),
+        ("arrow-cross", 1),
+        ("arrow-half-upper", 1),
+        ("arrow-thin-half-lower", 1),
+    ] {
+        let xpath = format!(
+            "//*[@marker-end = 'url(#{0})' or @marker-start = 'url(#{0})']",
+            marker
+        );
+        let value = evaluate_xpath(&document, &xpath).expect("failed to evaluate xpath");
+        assert_node_count(value, *count);
+    }
+
+    let value = evaluate_xpath(&document, "//*[local-name() = 'circle']")
+        .expect("failed to evaluate xpath");
+    assert_node_count(value, 2);
+}
+
+#[test]
+fn arrow_without_head() {
+    let diagram_str = "
+    @startuml
+    a -- b
+    @enduml";
+
+    assert!(seq_rs::parse(diagram_str, false).is_err());
+}
+
+#[test]
+fn arrow_ends_next_to_border() {
+    let diagram_str = "
+    @startuml
+    a ->x]: Lost
+    a ->o]: Circle
+    a ->o?: Short circle
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    let value = evaluate_xpath(&document, "//*[@marker-end = 'url(#arrow-cross)']")
+        .expect("failed to evaluate xpath");
+    assert_node_count(value, 1);
+    let value = evaluate_xpath(&document, "//*[local-name() = 'circle']")
+        .expect("failed to evaluate xpath");
+    assert_node_count(value, 2);
+}
+
+#[test]
+fn border_messages() {
+    let diagram_str = "
+    @startuml
+    [-> a: Incoming
+    a ->]: Outgoing
+    [<- a: Back to the left
+    ?-> b ++: Short incoming
+    b ->?: Short outgoing
+    note right: Next to the border message
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    for label in &[
+        "Incoming",
+        "Outgoing",
+        "Back to the left",
+        "Short incoming",
+        "Short outgoing",
+        "Next to the border message",
+    ] {
+        let value = find_text(&document, label);
+        assert_node_count(value, 1);
+    }
+
+    // The incoming message starts at the left border, next to the edge of the diagram
+    let value = evaluate_xpath(&document, "//*[@marker-end = 'url(#arrow)'][1]/@x1")
+        .expect("failed to evaluate xpath");
+    assert_eq!(value.string(), "10");
+}
+
+#[test]
+fn circled_border_messages() {
+    for diagram_str in &[
+        "@startuml\n[o-> a\n@enduml",
+        "@startuml\n[o->o a\n@enduml",
+        "@startuml\n?o-> a\n@enduml",
+        "@startuml\n?-> a\n@enduml",
+        "@startuml\na ->o]\n@enduml",
+    ] {
+        let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+        let package = parser::parse(&svg).expect("failed to parse SVG XML");
+        let document = package.as_document();
+
+        // The circles are drawn inside the diagram
+        let value = evaluate_xpath(&document, "//*[local-name() = 'circle'][@cx < 4]")
+            .expect("failed to evaluate xpath");
+        assert_node_count(value, 0);
+    }
+}
+
+#[test]
+fn participant_lifecycle() {
+    let diagram_str = "
+    @startuml
+    participant Server
+    create Worker
+    Server -> Worker: spawn
+    Server -> Cache **: new
+    Server -> Cache !!: drop
+    Worker -> Server: done
+    destroy Worker
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    // Destroyed participants are not drawn again at the bottom of the diagram
+    assert_node_count(find_text(&document, "Server"), 2);
+    assert_node_count(find_text(&document, "Worker"), 1);
+    assert_node_count(find_text(&document, "Cache"), 1);
+
+    let error = seq_rs::parse("@startuml\nA -> ] ** : x\n@enduml", false)
+        .expect_err("Creating the border should fail");
+    assert!(error.to_string().contains("Create"));
+}
+
+#[test]
+fn only_created_participants() {
+    for diagram_str in &[
+        "@startuml\ncreate A\ncreate B\nA -> B\n@enduml",
+        "@startuml\ncreate B\n[-> B : hi\n@enduml",
+    ] {
+        let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+        let package = parser::parse(&svg).expect("failed to parse SVG XML");
+        let document = package.as_document();
+
+        assert_node_count(find_text(&document, "B"), 2);
+    }
+}
+
+#[test]
+fn created_participant_without_message() {
+    let diagram_str = "
+    @startuml
+    a -> b
+    create c
+    @enduml";
+
+    assert!(seq_rs::parse(diagram_str, false).is_err());
+}
+
+#[test]
+fn autonumber() {
+    let diagram_str = "
+    @startuml
+    a -> b: Not numbered
+    autonumber
+    a -> b: First
+    autonumber 10 5 \"[000]\"
+    a -> b: Padded
+    b -> a: Stepped
+    autonumber stop
+    a -> b: Stopped
+    autonumber resume
+    a -> b: Resumed
+    autonumber 1.1.1
+    a -> b: Nested
+    autonumber inc A
+    a -> b: Incremented
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    for label in &[
+        "Not numbered",
+        "1 First",
+        "[010] Padded",
+        "[015] Stepped",
+        "Stopped",
+        "[020] Resumed",
+        "1.1.1 Nested",
+        "2.1.1 Incremented",
+    ] {
+        let value = find_text(&document, label);
+        assert_node_count(value, 1);
+    }
+
+    // The largest number can be used, but not incremented past
+    let last = "@startuml\nautonumber 4294967295\na -> b\n@enduml";
+    assert!(seq_rs::parse(last, false).is_ok());
+    let overflow = "@startuml\nautonumber 4294967295\na -> b\nb -> a\n@enduml";
+    assert!(seq_rs::parse(overflow, false).is_err());
+}
+
+#[test]
+fn delays_and_spacers() {
+    let diagram_str = "
+    @startuml
+    a -> b: Before
+    ...
+    a -> b: Short wait
+    ... 5 minutes later ...
+    |||
+    ||45||
+    b -> a: After
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    let value = find_text(&document, "5 minutes later");
+    assert_node_count(value, 1);
+
+    // Both lifelines are dotted across both delays
+    let value = evaluate_xpath(&document, "//*[@stroke-dasharray = '4']")
+        .expect("failed to evaluate xpath");
+    assert_node_count(value, 4);
+}
+
+#[test]
+fn return_messages() {
+    let diagram_str = "
+    @startuml
+    a -> b ++: Request
+    b -> c: Query
+    activate c
+    return Rows
+    return Multiline\\nresponse
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    for label in &["Rows", "Multiline", "response"] {
+        let value = find_text(&document, label);
+        assert_node_count(value, 1);
+    }
+
+    // Both replies are dashed
+    let value = evaluate_xpath(&document, "//*[@stroke-dasharray = '5']")
+        .expect("failed to evaluate xpath");
+    assert_node_count(value, 2);
+}
+
+#[test]
+fn return_requires_word_boundary() {
+    let diagram_str = "
+    @startuml
+    a -> b ++: Request
+    returned
+    @enduml";
+
+    assert!(seq_rs::parse(diagram_str, false).is_err());
+}
+
+#[test]
+fn return_without_activation() {
+    let diagram_str = "
+    @startuml
+    a -> b
+    return
+    @enduml";
+
+    assert!(seq_rs::parse(diagram_str, false).is_err());
+}
+
+#[test]
+fn notes() {
+    let diagram_str = "
+    @startuml
+    a -> b: Message
+    note left: Left of message
+    note on link: On link
+    note left of b: Left of b
+    note right of a
+    Multiline
+    right of a
+    end note
+    note over a, b: Over both
+    note across: Across everything
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    for label in &[
+        "Left of message",
+        "On link",
+        "Left of b",
+        "Multiline",
+        "right of a",
+        "Over both",
+        "Across everything",
+    ] {
+        let value = find_text(&document, label);
+        assert_node_count(value, 1);
+    }
+}
+
+#[test]
+fn note_shapes_and_colours() {
+    let diagram_str = "
+    @startuml
+    a -> b
+    hnote over a #lightgreen: Hexagon
+    rnote over b #FFAAAA
+    Rectangle
+    end rnote
+    note over a, b: Default
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    for (fill, count) in &[("lightgreen", 1), ("#FFAAAA", 1), ("#dedede", 1)] {
+        let xpath = format!("//*[@fill = '{}']", fill);
+        let value = evaluate_xpath(&document, &xpath).expect("failed to evaluate xpath");
+        assert_node_count(value, *count);
+    }
+}
+
+#[test]
+fn groups() {
+    let diagram_str = "
+    @startuml
+    loop 10 times
+    a -> b: In loop
+    end
+    opt optional
+    par
+    a -> c: First
+    else
+    b -> c: Second
+    end
+    end
+    critical
+    break failure
+    c -> a: Abort
+    end
+    end
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    for label in &[
+        "loop",
+        "opt",
+        "par",
+        "critical",
+        "break",
+        "[10 times]",
+        "[failure]",
+    ] {
+        let value = find_text(&document, label);
+        assert_node_count(value, 1);
+    }
+
+    // Groups without messages span the whole diagram
+    for diagram_str in &[
+        "@startuml\nA -> B\nloop\n...\nend\n@enduml",
+        "@startuml\nA -> B\nloop\n|||\nend\n@enduml",
+    ] {
+        let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+        let package = parser::parse(&svg).expect("failed to parse SVG XML");
+        let document = package.as_document();
+        assert_node_count(find_text(&document, "loop"), 1);
+    }
+}
+
+#[test]
+fn else_requires_alt_or_par() {
+    let diagram_str = "
+    @startuml
+    loop
+    a -> b
+    else
+    b -> a
+    end
+    @enduml";
+
+    assert!(seq_rs::parse(diagram_str, false).is_err());
+}
+
+#[test]
+fn comments_are_ignored() {
+    let commented = "
+    ' Leading comment
+    @startuml
+    ' A comment line
+    participant A ' trailing comment
+    /' A block comment
+    spanning lines '/
+    A -> B: It's a label /' inline block '/
+    ' A comment inside the group
+    loop forever
+    B -> A
+    end
+    note over A
+    ' not part of the note
+    Note /' hidden '/text
+    end note
+    ... it's late ...
+    == Don't stop ==
+    @enduml";
+    let plain = "
+    @startuml
+    participant A
+    A -> B: It's a label
+    loop forever
+    B -> A
+    end
+    note over A
+    Note text
+    end note
+    ... it's late ...
+    == Don't stop ==
+    @enduml";
+
+    let commented_svg = seq_rs::parse(commented, false).expect("Parsing failed");
+    let plain_svg = seq_rs::parse(plain, false).expect("Parsing failed");
+    assert_eq!(commented_svg, plain_svg);
+}
+
+#[test]
+fn labels_starting_with_apostrophes() {
+    let diagram_str = "
+    @startuml
+    title 'Quoted' title
+    A -> B : 'quoted' text
+    activate B
+    return 'back' again
+    loop 'forever'
+    A -> B : x
+    end
+    alt 'first'
+    A -> B : y
+    else 'second'
+    A -> B : z
+    end
+    note left of A : 'noted' here
+    == 'Phase 2' ==
+    ... 'some' time ...
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    for label in &[
+        "'Quoted' title",
+        "'quoted' text",
+        "'back' again",
+        "['forever']",
+        "['first']",
+        "['second']",
+        "'noted' here",
+        "'Phase 2'",
+        "'some' time",
+    ] {
+        // The labels contain apostrophes, so they are quoted differently from find_text
+        let xpath = format!("//text()[normalize-space() = \"{}\"]", label);
+        let value = evaluate_xpath(&document, &xpath).expect("failed to evaluate xpath");
+        assert_node_count(value, 1);
+    }
+}
+
+#[test]
+fn title_header_footer_caption_and_legend() {
+    let diagram_str = "
+    @startuml
+    header Page header
+    title
+    A multiline
+    title
+    end title
+    caption The caption
+    footer Page footer\\nwith two lines
+    legend right
+    Legend line 1
+    Legend line 2
+    end legend
+    A -> B
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    for text in &[
+        "Page header",
+        "A multiline",
+        "title",
+        "The caption",
+        "Page footer",
+        "with two lines",
+        "Legend line 1",
+        "Legend line 2",
+    ] {
+        assert_node_count(find_text(&document, text), 1);
+    }
+
+    // The participants are pushed down below the header and the title
+    let value = evaluate_xpath(&document, "//*[local-name() = 'rect'][1]/@y")
+        .expect("failed to evaluate xpath");
+    assert!(value.number() > 60.0);
+
+    // The footer is the last thing in the diagram
+    let texts = evaluate_xpath(&document, "//*[local-name() = 'text'][last()]")
+        .expect("failed to evaluate xpath");
+    assert!(texts.string().contains("with two lines"));
+
+    // A diagram can consist of decorations only
+    let svg = seq_rs::parse(
+        "@startuml\ntitle Only a title\nlegend\nOnly a legend\nend legend\n@enduml",
+        false,
+    )
+    .expect("Parsing failed");
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+    assert_node_count(find_text(&document, "Only a title"), 1);
+    assert_node_count(find_text(&document, "Only a legend"), 1);
+}
+
+#[test]
+fn participant_boxes() {
+    let diagram_str = "
+    @startuml
+    box \"Frontend\" #LightBlue
+    participant A
+    end box
+    box \"Backend services\"
+    participant B
+    participant C
+    end box
+    A -> B
+    B -> C
+    C -> D
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    assert_node_count(find_text(&document, "Frontend"), 1);
+    assert_node_count(find_text(&document, "Backend services"), 1);
+
+    // The boxes are drawn first, behind the participants
+    let value = evaluate_xpath(&document, "//*[local-name() = 'rect'][1]/@fill")
+        .expect("failed to evaluate xpath");
+    assert_eq!(value.string(), "lightblue");
+
+    let result = seq_rs::parse("@startuml\nbox\nparticipant A\n@enduml", false);
+    assert!(result.is_err());
+
+    let result = seq_rs::parse("@startuml\nbox \"Empty\"\nend box\n@enduml", false);
+    assert!(result.is_err());
+}
+
+#[test]
+fn skinparams() {
+    let diagram_str = "
+    @startuml
+    skinparam ParticipantBackgroundColor #ffeeaa
+    skinparam sequence {
+      ' Keys in the block get the sequence prefix
+      LifeLineBorderColor Red
+      lifelineStrategy nosolid
+    }
+    skinparam maxMessageSize 100
+    skinparam responseMessageBelowArrow true
+    skinparam style strictuml
+    skinparam UnknownKey 5
+    skinparam noteFontSize big
+    A -> B: a message long enough to wrap
+    B --> A: reply
+    @enduml";
+
+    let (svg, warnings) = seq_rs::parse_with_warnings(diagram_str, false).expect("Parsing failed");
+    assert_eq!(warnings.len(), 2);
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    let count = |xpath: &str| match evaluate_xpath(&document, xpath) {
+        Ok(Value::Nodeset(nodeset)) => nodeset.size(),
+        _ => panic!("failed to evaluate xpath"),
+    };
+    assert_eq!(count("//*[local-name() = 'rect'][@fill = '#ffeeaa']"), 4);
+    assert_eq!(
+        count("//*[local-name() = 'line'][@stroke = 'red'][@stroke-dasharray = '8']"),
+        2
+    );
+    assert_eq!(
+        count("//*[local-name() = 'line'][@marker-end = 'url(#arrow-thin)']"),
+        1
+    );
+
+    // The label is wrapped onto multiple lines
+    for line in &["a message", "long enough", "to wrap"] {
+        assert_node_count(find_text(&document, line), 1);
+    }
+
+    // The reply's label is below its arrow
+    let value = |xpath: &str| {
+        evaluate_xpath(&document, xpath)
+            .expect("failed to evaluate xpath")
+            .number()
+    };
+    let reply_y = value("//*[local-name() = 'text'][normalize-space() = 'reply']/@y");
+    let arrow_y = value("//*[local-name() = 'line'][@marker-end = 'url(#arrow-thin)']/@y1");
+    assert!(reply_y > arrow_y);
+}
+
+#[test]
+fn footbox_and_unlinked_participants() {
+    let diagram_str = "
+    @startuml
+    hide footbox
+    hide unlinked
+    box \"Team\"
+    participant Unused
+    participant A
+    end box
+    participant Other
+    note over Other: keeps Other
+    A -> B
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    // The participants are only drawn at the top
+    assert_node_count(find_text(&document, "A"), 1);
+    assert_node_count(find_text(&document, "B"), 1);
+    assert_node_count(find_text(&document, "Other"), 1);
+    assert_node_count(find_text(&document, "Unused"), 0);
+    assert_node_count(find_text(&document, "Team"), 1);
+
+    let svg = seq_rs::parse(
+        "@startuml\nhide footbox\nshow footbox\nA -> B\n@enduml",
+        false,
+    )
+    .expect("Parsing failed");
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+    assert_node_count(find_text(&document, "A"), 2);
+
+    // Hiding every participant leaves an empty diagram
+    let svg = seq_rs::parse("@startuml\nhide unlinked\nparticipant X\n@enduml", false)
+        .expect("Parsing failed");
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+    assert_node_count(find_text(&document, "X"), 0);
+}
+
+#[test]
+fn creole_markup() {
+    let diagram_str = "
+    @startuml
+    A -> B: **bold** and //italic//
+    B -> A: \"\"mono\"\" --struck-- <color:red>red</color>
+    note over A: <size:18>big</size> [[http://example.com link]]
+    A -> B: a -- b
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    let count = |xpath: &str| evaluate_xpath(&document, xpath).expect("failed to evaluate xpath");
+    assert_node_count(
+        count("//*[@font-weight='bold' and normalize-space()='bold']"),
+        1,
+    );
+    assert_node_count(
+        count("//*[@font-style='italic' and normalize-space()='italic']"),
+        1,
+    );
+    assert_node_count(
+        count("//*[@font-family='monospace' and normalize-space()='mono']"),
+        1,
+    );
+    assert_node_count(
+        count("//*[@text-decoration='line-through' and normalize-space()='struck']"),
+        1,
+    );
+    assert_node_count(count("//*[@fill='red' and normalize-space()='red']"), 1);
+    assert_node_count(count("//*[@font-size='18' and normalize-space()='big']"), 1);
+    assert_node_count(
+        count("//*[local-name()='a' and @href='http://example.com']"),
+        1,
+    );
+    assert_node_count(
+        count("//text()[contains(., '**') or contains(., '<color')]"),
+        0,
+    );
+    // Markup that isn't closed is kept as text
+    assert_node_count(find_text(&document, "a -- b"), 1);
+}
+
+#[test]
+fn multiline_labels() {
+    let diagram_str = "
+    @startuml
+    participant \"Multi\\nLine\" as M
+    M -> B: first\\nsecond%newline()third
+    alt case\\none
+    B -> M
+    else case\\ntwo
+    B -> M
+    end
+    == sep\\narator ==
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    for line in &[
+        "Multi", "Line", "first", "second", "third", "[case", "one]", "[case", "two]", "sep",
+        "arator",
+    ] {
+        match find_text(&document, line) {
+            Value::Nodeset(nodeset) => assert!(nodeset.size() > 0, "{} not found", line),
+            _ => panic!("expected nodeset, got something else"),
+        }
+    }
+    assert_node_count(
+        evaluate_xpath(
+            &document,
+            "//text()[contains(., '\\n') or contains(., '%newline')]",
+        )
+        .expect("failed to evaluate xpath"),
+        0,
+    );
+}
+
+#[test]
+fn wrapped_labels() {
+    let diagram_str = "
+    @startuml
+    skinparam maxMessageSize 100
+    skinparam maxNoteSize 80
+    A -> B: a message long enough to wrap
+    note over A: a note that is long enough to wrap
+    @enduml";
+
+    let (svg, warnings) = seq_rs::parse_with_warnings(diagram_str, false).expect("Parsing failed");
+    assert!(warnings.is_empty());
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    for line in &[
+        "a message",
+        "long enough",
+        "to wrap",
+        "a note that",
+        "is long",
+        "enough to",
+    ] {
+        assert_node_count(find_text(&document, line), 1);
+    }
+
+    // The styles carry over to the lines a styled label is wrapped on
+    let diagram_str = "
+    @startuml
+    skinparam maxMessageSize 60
+    A -> B : **bold label that is long enough to wrap around**
+    @enduml";
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+    let count = |xpath: &str| evaluate_xpath(&document, xpath).expect("failed to evaluate xpath");
+    assert_node_count(count("//text()[contains(., '**')]"), 0);
+    for line in &["bold", "around"] {
+        let xpath = format!(
+            "//*[local-name()='tspan' and @font-weight='bold'][normalize-space() = '{}']",
+            line
+        );
+        assert_node_count(count(&xpath), 1);
+    }
+}
+
+#[test]
+fn font_metrics() {
+    let diagram_width = |label: &str| {
+        let diagram_str = format!("@startuml\nparticipant \"{}\" as A\nA -> A\n@enduml", label);
+        let svg = seq_rs::parse(&diagram_str, false).expect("Parsing failed");
+        let package = parser::parse(&svg).expect("failed to parse SVG XML");
+        let document = package.as_document();
+        let view_box = evaluate_xpath(&document, "string(/*/@viewBox)")
+            .expect("failed to evaluate xpath")
+            .string();
+        view_box.split(' ').nth(2).unwrap().parse::<u32>().unwrap()
+    };
+    // Wide characters take up twice the space of ASCII characters
+    assert!(diagram_width("漢字漢字漢字漢字") > diagram_width("abcdefgh"));
+    assert!(diagram_width("漢字漢字漢字漢字") < diagram_width("abcdefghabcdefgh") + 10);
+
+    let diagram_str = "
+    @startuml
+    skinparam defaultFontName Helvetica
+    skinparam noteFontName \"Times New Roman\"
+    skinparam arrowFontName Unknown
+    A -> B: message
+    note over A: note
+    @enduml";
+
+    let (svg, warnings) = seq_rs::parse_with_warnings(diagram_str, false).expect("Parsing failed");
+    assert_eq!(warnings.len(), 1);
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    let count = |xpath: &str| evaluate_xpath(&document, xpath).expect("failed to evaluate xpath");
+    assert_node_count(
+        count("//*[@font-family='Helvetica' and normalize-space()='A']"),
+        2,
+    );
+    assert_node_count(
+        count("//*[@font-family='Times New Roman' and normalize-space()='note']"),
+        1,
+    );
+    assert_node_count(
+        count("//*[@font-family='Helvetica' and normalize-space()='message']"),
+        1,
+    );
+}
+
+#[test]
+fn unicode_and_quoted_participant_names() {
+    let diagram_str = "
+    @startuml
+    participant \"Payment Gateway\" as PG
+    Zahlungsdienst -> 用户: hallo
+    order-service->\"Payment Gateway\": pay
+    \"Payment Gateway\" --> order-service
+    \"Fraud Check\" -> PG
+    activate \"Fraud Check\"
+    note over order-service, 用户: done
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    // Each participant is drawn at the top and at the bottom
+    assert_node_count(find_text(&document, "Payment Gateway"), 2);
+    assert_node_count(find_text(&document, "Zahlungsdienst"), 2);
+    assert_node_count(find_text(&document, "用户"), 2);
+    assert_node_count(find_text(&document, "order-service"), 2);
+    assert_node_count(find_text(&document, "Fraud Check"), 2);
+    assert_node_count(find_text(&document, "PG"), 0);
+}
+
+#[test]
+fn participant_aliases_and_order() {
+    let diagram_str = "
+    @startuml
+    participant Last order 30
+    participant \"First\" as F order 10
+    participant M as \"Middle
+    Participant\" order 20
+    F -> M
+    M -> Last
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    let x = |label: &str| {
+        let xpath = format!("string((//*[normalize-space() = '{}'])[1]/@x)", label);
+        evaluate_xpath(&document, &xpath)
+            .expect("failed to evaluate xpath")
+            .number()
+    };
+    assert_node_count(find_text(&document, "Middle"), 2);
+    assert_node_count(find_text(&document, "Participant"), 2);
+    assert!(x("First") < x("Middle"));
+    assert!(x("Middle") < x("Last"));
+}
+
+#[test]
+fn participant_order_out_of_range() {
+    let diagram_str = "
+    @startuml
+    participant X order 99999999999
+    @enduml";
+
+    assert!(seq_rs::parse(diagram_str, false).is_err());
+}
+
+#[test]
+fn participant_stereotypes_and_colours() {
+    let diagram_str = "
+    @startuml
+    participant API <<REST>> #lightblue
+    participant Svc << (C,#ADD1B2) Service >>
+    actor User #pink
+    User -> API
+    API -> Svc
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    let count = |xpath: &str| evaluate_xpath(&document, xpath).expect("failed to evaluate xpath");
+    assert_node_count(find_text(&document, "«REST»"), 2);
+    assert_node_count(find_text(&document, "«Service»"), 2);
+    assert_node_count(find_text(&document, "C"), 2);
+    assert_node_count(count("//*[local-name()='rect' and @fill='lightblue']"), 2);
+    assert_node_count(count("//*[local-name()='circle' and @fill='#ADD1B2']"), 2);
+    assert_node_count(count("//*[local-name()='circle' and @fill='pink']"), 2);
+}
+
+#[test]
+fn arrow_styles() {
+    let diagram_str = "
+    @startuml
+    A -[#red,bold]> B : coloured
+    B -[dotted]-> A : dotted
+    A -[hidden]> B : hidden
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    let count = |xpath: &str| evaluate_xpath(&document, xpath).expect("failed to evaluate xpath");
+    assert_node_count(
+        count("//*[local-name()='line' and @stroke='red' and @stroke-width='2']"),
+        1,
+    );
+    assert_node_count(count("//*[local-name()='marker' and @id='arrow-red']"), 1);
+    assert_node_count(count("//*[@marker-end='url(#arrow-red)']"), 1);
+    assert_node_count(
+        count("//*[local-name()='line' and @stroke-dasharray='2']"),
+        1,
+    );
+    assert_node_count(find_text(&document, "hidden"), 0);
+
+    let error = seq_rs::parse("@startuml\nA -[wavy]> B\n@enduml", false);
+    assert!(error.is_err());
+}
+
+#[test]
+fn activation_colours_and_autoactivate() {
+    let diagram_str = "
+    @startuml
+    autoactivate on
+    A -> B : call
+    B -> C ++ #gold : nested
+    C --> B : reply
+    B --> A : done
+    A ->> B : async
+    A ->x B : lost
+    autoactivate off
+    activate A #FFBBBB
+    A -> B : plain
+    deactivate A
+    @enduml";
+
+    let svg = seq_rs::parse(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    // Activation boxes are the only rectangles ten pixels wide
+    let count = |xpath: &str| evaluate_xpath(&document, xpath).expect("failed to evaluate xpath");
+    assert_node_count(count("//*[local-name()='rect' and @width='10']"), 3);
+    assert_node_count(
+        count("//*[local-name()='rect' and @width='10' and @fill='gold']"),
+        1,
+    );
+    assert_node_count(
+        count("//*[local-name()='rect' and @width='10' and @fill='#FFBBBB']"),
+        1,
+    );
+}
+
+#[test]
+fn parallel_messages() {
+    let diagram_str = "
+    @startuml
+    Alice -> Bob : hello
+    & Bob -> Charlie : hi
+    Alice -> Charlie : both
+    & Charlie -> Alice : overlap
+    @enduml";
+
+    let (svg, warnings) = seq_rs::parse_with_warnings(diagram_str, false).expect("Parsing failed");
+
+    let package = parser::parse(&svg).expect("failed to parse SVG XML");
+    let document = package.as_document();
+
+    let y = |label: &str| {
+        let xpath = format!("string((//*[normalize-space() = '{}'])[1]/@y)", label);
+        evaluate_xpath(&document, &xpath)
+            .expect("failed to evaluate xpath")
+            .number()
+    };
+    assert_eq!(y("hello"), y("hi"));
+    assert!(y("both") > y("hello"));
+    assert!(y("overlap") > y("both"));
+    assert_eq!(warnings.len(), 1);
+
+    let error = seq_rs::parse("@startuml\n& A -> B\n@enduml", false);
+    assert!(error.is_err());
+}
+
 fn assert_node_count(value: Value, count: usize) {
     match value {
         Value::Nodeset(nodeset) => assert_eq!(nodeset.size(), count),
@@ -73,5 +1182,5 @@ fn assert_node_count(value: Value, count: usize) {
 
 fn find_text<'a>(document: &'a Document, text: &str) -> Value<'a> {
     let xpath = format!("//text()[normalize-space() = '{}']", text);
-    evaluate_xpath(&document, &xpath).expect("failed to evaluate xpath")
+    evaluate_xpath(document, &xpath).expect("failed to evaluate xpath")
 }